@@ -0,0 +1,488 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::diagnostic::message;
+use crate::program::Version;
+
+/// filter to use when downsampling supersampled renders
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Downsample {
+    Box,
+    Lanczos,
+}
+
+impl FromStr for Downsample {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "box" => Ok(Self::Box),
+            "lanczos" => Ok(Self::Lanczos),
+            _ => Err(()),
+        }
+    }
+}
+
+/// how often to draw frames in the window
+#[derive(Copy, Clone, PartialEq)]
+pub enum FrameRate {
+    // at most this many frames per second
+    Capped(f32),
+    // as fast as possible
+    Uncapped,
+}
+
+impl FromStr for FrameRate {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uncapped" => Ok(Self::Uncapped),
+            x => match x.parse::<f32>() {
+                Ok(x) if x > 0.0 => Ok(Self::Capped(x)),
+                _ => Err(()),
+            },
+        }
+    }
+}
+
+/// how to print compile errors
+#[derive(Copy, Clone, PartialEq, Eq, Default)]
+pub enum MessageFormat {
+    // as the driver gives them, with file names
+    #[default]
+    Human,
+    // one json object per line, in the same form for every driver
+    Json,
+}
+
+impl FromStr for MessageFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// what to do
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Command {
+    // show the shader in a window, or render it offline if there's an output
+    Run,
+    // measure how long frames take
+    Bench,
+    // check the shaders compile, without a window or gpu
+    Check,
+    // run a language server for editors
+    Lsp,
+}
+
+/// settings given on the command line
+pub struct Arguments {
+    // what to do
+    pub command: Command,
+
+    // path to the shader or config file
+    pub file_path: String,
+
+    // what resolution to render at compared to the window resolution
+    pub render_scale: f32,
+
+    // how many samples to accumulate in the main image, if any
+    pub accumulate: Option<u32>,
+
+    // where to write the rendered image to, renders offline if set
+    // or where to write the benchmark results to
+    pub output: Option<PathBuf>,
+
+    // resolution to use when rendering offline
+    pub size: (u32, u32),
+
+    // how many frames to render when rendering offline or benchmarking
+    pub frames: Option<u32>,
+
+    // supersampling factor when rendering offline
+    pub supersample: u32,
+
+    // how to downsample supersampled renders
+    pub downsample: Downsample,
+
+    // how many sub-frames to average for motion blur when rendering offline
+    pub motion_blur: u32,
+
+    // shutter angle for motion blur, in degrees, 360 is the entire frame
+    pub shutter: f32,
+
+    // time to start at, in seconds
+    pub start_time: f32,
+
+    // how fast time goes, 1 is realtime
+    pub speed: f32,
+
+    // whether to start paused
+    pub paused: bool,
+
+    // how far to jump in time with the arrow keys, in seconds
+    pub skip: f32,
+
+    // frames per second to compute time from, instead of measuring it
+    pub fixed: Option<f32>,
+
+    // how often to draw frames, if not set by the config
+    pub fps: Option<FrameRate>,
+
+    // whether to wait for vsync, if not set by the config
+    pub vsync: Option<bool>,
+
+    // values mapped to 0 and 1 when remapping in the debug view
+    pub range: (f32, f32),
+
+    // whether to look for NaN and Inf in every pass
+    pub check_nan: bool,
+
+    // directories to look for includes in, after the project directory
+    pub include_path: Vec<PathBuf>,
+
+    // defines for every pass, as name and value, these take priority over the config
+    pub defines: Vec<(String, String)>,
+
+    // glsl version to compile shaders as, if not set by the config
+    pub version: Option<Version>,
+
+    // how to print compile errors
+    pub message_format: MessageFormat,
+
+    // whether to keep compiled programs on disk, to load them quicker the next time
+    pub cache: bool,
+}
+
+impl Arguments {
+    /// parse the arguments, without the program name
+    /// returns None if the help menu should be shown instead
+    pub fn parse(args: &[String]) -> Option<Self> {
+        // subcommands go first
+        let (command, args) = match args.first().map(String::as_str) {
+            Some("bench") => (Command::Bench, &args[1..]),
+            Some("check") => (Command::Check, &args[1..]),
+            Some("lsp") => (Command::Lsp, &args[1..]),
+            _ => (Command::Run, args),
+        };
+
+        // errors are printed in the format asked for, even before it's parsed
+        let format = message_format(args);
+
+        let mut file_path = None;
+        let mut render_scale = 1.0;
+        let mut accumulate = None;
+        let mut output = None;
+        let mut size = (800, 450);
+        let mut frames = None;
+        let mut supersample = 1;
+        let mut downsample = Downsample::Box;
+        let mut motion_blur = 1;
+        let mut shutter = 180.0;
+        let mut start_time = 0.0;
+        let mut speed = 1.0;
+        let mut paused = false;
+        let mut skip = 1.0;
+        let mut fixed = None;
+        let mut fps = None;
+        let mut vsync = None;
+        let mut range = (0.0, 1.0);
+        let mut check_nan = false;
+        let mut include_path = Vec::new();
+        let mut defines = Vec::new();
+        let mut version = None;
+        let mut message_format = MessageFormat::Human;
+        let mut cache = true;
+
+        // go over all arguments
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            // --option=value is the same as --option value
+            let (arg, inline) = match arg.split_once('=') {
+                Some((name, value)) if arg.starts_with("--") => (name, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mut next = || inline.as_ref().or_else(|| args.next());
+
+            match arg {
+                "-s" | "--scale" => render_scale = parse_value(arg, next(), format),
+                "-a" | "--accumulate" => accumulate = Some(parse_positive(arg, next(), format)),
+                "-o" | "--output" => output = Some(parse_value(arg, next(), format)),
+                "--size" => size = parse_size(arg, next(), format),
+                "--frames" => frames = Some(parse_positive(arg, next(), format)),
+                "--ssaa" => supersample = parse_value(arg, next(), format),
+                "--downsample" => downsample = parse_value(arg, next(), format),
+                "--motion-blur" => motion_blur = parse_value(arg, next(), format),
+                "--shutter" => shutter = parse_value(arg, next(), format),
+                "-t" | "--time" => start_time = parse_value(arg, next(), format),
+                "--speed" => speed = parse_value(arg, next(), format),
+                "--paused" => paused = true,
+                "--skip" => skip = parse_value(arg, next(), format),
+                "--fixed" => fixed = Some(parse_rate(arg, next(), format)),
+                "--fps" => fps = Some(parse_value(arg, next(), format)),
+                "--vsync" => vsync = Some(true),
+                "--no-vsync" => vsync = Some(false),
+                "--range" => range = parse_range(arg, next(), format),
+                "--check-nan" => check_nan = true,
+                "-I" | "--include" => include_path.push(parse_value(arg, next(), format)),
+                "-D" | "--define" => defines.push(parse_define(arg, next(), format)),
+                "--glsl" => version = Some(parse_value(arg, next(), format)),
+                "--message-format" => message_format = parse_value(arg, next(), format),
+                "--no-cache" => cache = false,
+                "-h" | "--help" => return None,
+                // anything else is the path, but only once
+                x if file_path.is_none() && !x.starts_with('-') => file_path = Some(x.to_string()),
+                x => {
+                    let text = format!("Unknown argument: {}, see --help for the options", x);
+                    message(format, &text);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Some(Self {
+            command,
+            file_path: file_path?,
+            render_scale,
+            accumulate,
+            output,
+            size,
+            frames,
+            supersample: supersample.max(1),
+            downsample,
+            motion_blur: motion_blur.max(1),
+            shutter,
+            start_time,
+            speed,
+            paused,
+            skip,
+            fixed,
+            fps,
+            vsync,
+            range,
+            check_nan,
+            include_path,
+            defines,
+            version,
+            message_format,
+            cache,
+        })
+    }
+
+    /// show the help menu
+    pub fn print_help() {
+        println!("Shadercrab {}", env!("CARGO_PKG_VERSION"));
+        println!("A simple shadertoy emulator");
+        println!("Usage:");
+        println!("shadercrab [path] [options]");
+        println!("shadercrab bench [path] [options]");
+        println!("shadercrab check [path] [options]");
+        println!("shadercrab lsp [path] [options]");
+        println!("	path: path to the shader file to use");
+        println!("	bench: render frames as fast as possible, and report how long they took");
+        println!("	       frames are rendered at the given size, and results are written as json to the output");
+        println!("	check: check that every pass compiles, without a window or gpu, and exit with an error if not");
+        println!("	       errors are printed as file:line:column: error: message");
+        println!("	lsp: run a language server for the project over stdin and stdout, for editors");
+        println!("	     with completion and hover for builtins, go to definition, and errors from check when saving");
+        println!("Options:");
+        println!("	options starting with -- can also be given as --option=value");
+        println!(
+            "	-s, --scale [scale]: what resolution to render at compared to window resolution"
+        );
+        println!(
+            "	-a, --accumulate [samples]: average this many frames of the main image, with jitter"
+        );
+        println!("	-o, --output [path]: render offline to the given image file, without opening a window");
+        println!("	--size [width]x[height]: resolution to render at when rendering offline, 800x450 by default");
+        println!(
+            "	--frames [frames]: how many frames to render when rendering offline, 1 by default"
+        );
+        println!("	                   or how many to measure when benchmarking, 500 by default");
+        println!(
+            "	                   when accumulating, renders until all samples are taken instead"
+        );
+        println!("	--ssaa [factor]: when rendering offline, render at factor times the resolution and downsample");
+        println!("	--downsample [box|lanczos]: filter to downsample with when supersampling, box by default");
+        println!("	--motion-blur [sub-frames]: when rendering offline, average this many sub-frames for the last frame");
+        println!("	--shutter [degrees]: shutter angle for motion blur, 360 is an entire frame, 180 by default");
+        println!("	-t, --time [seconds]: time to start at, 0 by default");
+        println!("	--speed [speed]: how fast time goes, 1 is realtime");
+        println!("	--paused: start with time paused");
+        println!(
+            "	--skip [seconds]: how far the left and right arrow keys jump in time, 1 by default"
+        );
+        println!("	--fixed [fps]: use a fixed timestep, where iTime is iFrame / fps, to get the same result on every run");
+        println!("	               offline renders always do this, at 60 fps unless set");
+        println!("	--fps [fps|uncapped]: how many frames to draw per second, 60 by default");
+        println!("	                      uncapped draws as fast as possible");
+        println!("	--vsync, --no-vsync: whether to wait for vsync, on by default");
+        println!("	--range [min]:[max]: values shown as 0 and 1 when remapping in the debug view, 0:1 by default");
+        println!("	--check-nan: look for NaN and Inf in every pass, shown in magenta and cyan, and report the first frame and pass with them");
+        println!("	             when rendering offline, exits with an error if any are found");
+        println!("	-I, --include [dir]: also look for #include <file> in this directory, can be given more than once");
+        println!("	-D, --define [name]=[value]: #define name as value in every pass, or as nothing if there's no value");
+        println!("	                             can be given more than once, and takes priority over defines in the config");
+        println!("	--glsl [140|330|450|es300]: glsl version to compile shaders as, 140 by default");
+        println!("	                            es300 is what shadertoy uses, to check if shaders will work there");
+        println!("	--message-format [human|json]: how to print compile errors, human by default");
+        println!("	                               json prints one object per line, with the pass, file, line, column, severity and message");
+        println!("	--no-cache: always compile shaders, instead of loading them from the cache of compiled shaders");
+        println!();
+        println!("This opens a window that shows the shader");
+        println!("The shader is reloaded when it, or any file it includes, is modified, or the r key is pressed");
+        println!();
+        println!("Keys:");
+        println!("	r: reload the shader, and go back to the start time");
+        println!("	space: pause or unpause time");
+        println!("	period: step a single frame, and pause");
+        println!("	left, right: jump backward or forward in time");
+        println!("	up, down: double or halve the speed");
+        println!("	f1: show or hide stats");
+        println!("	i: hold to show the value of the pixel under the cursor");
+        println!("	f2: switch between the main image, all passes, and a single pass");
+        println!("	f3: switch between showing values as is, remapped to the range, and remapped with false colour");
+        println!("	f4: start or stop looking for NaN and Inf");
+        println!(
+            "	1-9: pass to show on its own and to inspect, main, then the others sorted by name"
+        );
+        println!(
+            "	middle mouse button: print the values passed to debugPrint at the clicked pixel"
+        );
+        println!("	keys bound to toggles in the config: define or undefine them, and recompile");
+        println!("Any shader errors are printed to the terminal");
+        println!();
+        println!("Shader format:");
+        println!("Shaders are in glsl, and need the function");
+        println!("	mainImage(out vec4 fragColor, in vec2 fragCoord)");
+        println!("where");
+        println!("	fragColor: output color for the pixel, in sRGB color space");
+        println!("	fragCoord: the pixel coordinate, with bottom left at (0, 0) and top right at (width, height)");
+        println!("	           width and height are the width and height of the window");
+        println!();
+        println!("The following constants are also defined:");
+        println!("	float iTime: seconds since the shader was loaded");
+        println!("	float iTimeDelta: seconds between the last two frames");
+        println!("	float iFrameRate: frames drawn per second");
+        println!("	int iFrame: current frame number");
+        println!("	int iSampleIndex: current sample when accumulating, 0 otherwise");
+        println!("	vec3 iResolution: width, height and aspect ratio (y / x) of the window");
+        println!(
+            "	vec3 iChannelResolution[4]: resolution of each input, in the same way as iResolution"
+        );
+        println!(
+            "	vec4 iMouse: xy: mouse position, changed when dragging with the left mouse button"
+        );
+        println!("	             zw: mouse button states (0 is up, 1 is down)");
+        println!();
+        println!("And the function debugPrint(value), which prints an int, float or vector at the clicked pixel");
+    }
+}
+
+// parse the value of an argument, or quit if it's not valid
+fn parse_value<T: FromStr>(arg: &str, value: Option<&String>, format: MessageFormat) -> T {
+    match value.map(|x| x.parse::<T>()) {
+        Some(Ok(x)) => x,
+        Some(Err(_)) => fail(format, &format!("Could not parse the value of {}", arg)),
+        None => fail(format, &format!("Missing value for {}", arg)),
+    }
+}
+
+// print why the arguments are wrong, and quit
+fn fail(format: MessageFormat, text: &str) -> ! {
+    message(format, text);
+    std::process::exit(1);
+}
+
+// the message format, if it's given, which is the last one like when parsing
+fn message_format(args: &[String]) -> MessageFormat {
+    let mut format = MessageFormat::default();
+    for (i, arg) in args.iter().enumerate() {
+        let value = match arg.strip_prefix("--message-format") {
+            Some("") => args.get(i + 1).map(String::as_str),
+            Some(x) => x.strip_prefix('='),
+            None => None,
+        };
+        if let Some(x) = value.and_then(|x| x.parse().ok()) {
+            format = x;
+        }
+    }
+    format
+}
+
+// parse a number that's at least 1
+fn parse_positive(arg: &str, value: Option<&String>, format: MessageFormat) -> u32 {
+    match parse_value(arg, value, format) {
+        0 => fail(
+            format,
+            &format!(
+                "Could not parse the value of {}, it should be at least 1",
+                arg
+            ),
+        ),
+        x => x,
+    }
+}
+
+// parse a number of frames per second, which has to be more than 0
+fn parse_rate(arg: &str, value: Option<&String>, format: MessageFormat) -> f32 {
+    match parse_value(arg, value, format) {
+        x if x > 0.0 => x,
+        _ => fail(
+            format,
+            &format!(
+                "Could not parse the value of {}, it should be more than 0",
+                arg
+            ),
+        ),
+    }
+}
+
+// parse a size, in the form of [width]x[height]
+fn parse_size(arg: &str, value: Option<&String>, format: MessageFormat) -> (u32, u32) {
+    let value: String = parse_value(arg, value, format);
+    match value.split_once('x').map(|(w, h)| (w.parse(), h.parse())) {
+        Some((Ok(w), Ok(h))) if w > 0 && h > 0 => (w, h),
+        _ => fail(
+            format,
+            &format!(
+                "Could not parse the size {}, expected [width]x[height]",
+                value
+            ),
+        ),
+    }
+}
+
+// parse a range, in the form of [min]:[max]
+fn parse_range(arg: &str, value: Option<&String>, format: MessageFormat) -> (f32, f32) {
+    let value: String = parse_value(arg, value, format);
+    match value.split_once(':').map(|(a, b)| (a.parse(), b.parse())) {
+        Some((Ok(min), Ok(max))) if min != max => (min, max),
+        _ => fail(
+            format,
+            &format!("Could not parse the range {}, expected [min]:[max]", value),
+        ),
+    }
+}
+
+// parse a define, in the form of [name]=[value] or [name]
+fn parse_define(arg: &str, value: Option<&String>, format: MessageFormat) -> (String, String) {
+    let define: String = parse_value(arg, value, format);
+    let (name, value) = define.split_once('=').unwrap_or((&define, ""));
+    if name.is_empty() {
+        fail(
+            format,
+            &format!(
+                "Could not parse the define {}, expected [name]=[value]",
+                define
+            ),
+        );
+    }
+    (name.to_string(), value.to_string())
+}
@@ -0,0 +1,179 @@
+use serde::Serialize;
+use std::time::Instant;
+
+use crate::args::Arguments;
+use crate::buffer::FrameInfo;
+use crate::clock::Clock;
+use crate::diagnostic::message;
+use crate::drawer::Drawer;
+use crate::parser::Shadertoy;
+use crate::render::{hidden_display, DEFAULT_FPS};
+
+// how many frames to measure, if not set
+const DEFAULT_FRAMES: u32 = 500;
+
+// frames to render before measuring, so startup costs don't show up in the results
+const WARMUP_FRAMES: u32 = 10;
+
+/// minimum, mean and 99th percentile of a set of times, in milliseconds
+#[derive(Serialize)]
+pub struct Stats {
+    pub min: f32,
+    pub mean: f32,
+    pub p99: f32,
+}
+
+impl Stats {
+    // compute from a list of times, None if there are none
+    fn new(times: &mut [f32]) -> Option<Self> {
+        if times.is_empty() {
+            return None;
+        }
+
+        times.sort_by(f32::total_cmp);
+        let p99 = ((times.len() as f32 * 0.99).ceil() as usize).clamp(1, times.len()) - 1;
+
+        Some(Self {
+            min: times[0],
+            mean: times.iter().sum::<f32>() / times.len() as f32,
+            p99: times[p99],
+        })
+    }
+}
+
+/// timings of a single pass
+#[derive(Serialize)]
+pub struct PassResult {
+    pub name: String,
+    pub gpu_time: Stats,
+}
+
+/// full results of a benchmark
+#[derive(Serialize)]
+pub struct BenchResult {
+    pub file: String,
+    pub renderer: String,
+    pub version: String,
+    pub resolution: (u32, u32),
+    pub frames: u32,
+    pub frame_time: Stats,
+    pub passes: Vec<PassResult>,
+}
+
+/// render frames as fast as possible, and report how long they took
+pub fn bench(args: &Arguments, shadertoy: &Shadertoy) {
+    let (_event_loop, display) = hidden_display(args.size.0, args.size.1);
+
+    // the actual drawing manager, measuring every pass
+    let resolution = display.get_framebuffer_dimensions();
+    let mut drawer = Drawer::new(
+        &display,
+        resolution.0,
+        resolution.1,
+        args.render_scale,
+        None,
+    );
+    drawer.timing = true;
+    shadertoy.load_shaders(&display, &mut drawer);
+
+    // fixed timestep, so every run renders the same frames
+    let mut clock = Clock::new(
+        args.start_time,
+        args.speed,
+        false,
+        Some(args.fixed.unwrap_or(DEFAULT_FPS)),
+    );
+
+    let frames = args.frames.unwrap_or(DEFAULT_FRAMES);
+    let mut frame_times = Vec::new();
+    let mut gpu_times = vec![Vec::new(); drawer.buffers.len()];
+
+    for frame in 0..WARMUP_FRAMES + frames {
+        let start = Instant::now();
+        drawer.draw(
+            &display,
+            &FrameInfo {
+                time: clock.time(),
+                time_delta: clock.delta(),
+                frame_rate: clock.frame_rate(),
+                frame: clock.frame(),
+                ..Default::default()
+            },
+            args.render_scale,
+        );
+
+        // wait for the gpu to be done, so the entire frame is measured
+        display.finish();
+        let frame_time = start.elapsed().as_secs_f32() * 1000.0;
+        drawer.update_gpu_times();
+        clock.tick();
+
+        if frame >= WARMUP_FRAMES {
+            frame_times.push(frame_time);
+            for (times, buffer) in gpu_times.iter_mut().zip(&drawer.buffers) {
+                times.extend(buffer.gpu_time);
+            }
+        }
+    }
+
+    let result = BenchResult {
+        file: args.file_path.clone(),
+        renderer: display.get_opengl_renderer_string().to_string(),
+        version: display.get_opengl_version_string().to_string(),
+        resolution: (drawer.width, drawer.height),
+        frames,
+        frame_time: Stats::new(&mut frame_times).expect("no frames were measured"),
+        // passes that were never drawn, or not measured, are left out
+        passes: drawer
+            .buffers
+            .iter()
+            .zip(gpu_times.iter_mut())
+            .filter_map(|(buffer, times)| {
+                Some(PassResult {
+                    name: buffer.name.clone(),
+                    gpu_time: Stats::new(times)?,
+                })
+            })
+            .collect(),
+    };
+
+    // show the results, with json only the results themselves are on stdout
+    let format = args.message_format;
+    message(format, &format!("GPU: {}", result.renderer));
+    message(
+        format,
+        &format!(
+            "{} frames at {}x{}",
+            result.frames, result.resolution.0, result.resolution.1
+        ),
+    );
+    message(
+        format,
+        &format!(
+            "frame time: min {:.3}ms, mean {:.3}ms, p99 {:.3}ms",
+            result.frame_time.min, result.frame_time.mean, result.frame_time.p99
+        ),
+    );
+    for pass in &result.passes {
+        message(
+            format,
+            &format!(
+                "{} gpu time: min {:.3}ms, mean {:.3}ms, p99 {:.3}ms",
+                pass.name, pass.gpu_time.min, pass.gpu_time.mean, pass.gpu_time.p99
+            ),
+        );
+    }
+
+    // and write them out, for comparing later
+    let json = serde_json::to_string_pretty(&result).expect("failed to serialize results");
+    match &args.output {
+        Some(path) => {
+            if let Err(reason) = std::fs::write(path, json) {
+                let text = format!("Failed to write results to {:?}: {}", path, reason);
+                message(format, &text);
+                std::process::exit(1);
+            }
+        }
+        None => println!("{}", json),
+    }
+}
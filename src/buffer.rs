@@ -1,3 +1,4 @@
+use glium::uniforms::MagnifySamplerFilter;
 use glium::Surface;
 
 // vertex buffer
@@ -7,12 +8,38 @@ pub struct Vert {
 }
 glium::implement_vertex!(Vert, pos);
 
+/// inputs for the shaders that change every frame
+#[derive(Copy, Clone, Default)]
+pub struct FrameInfo {
+    // time since the start
+    pub time: f32,
+
+    // time since the last frame
+    pub time_delta: f32,
+
+    // frames per second
+    pub frame_rate: f32,
+
+    // frame counter
+    pub frame: i32,
+
+    // mouse position and buttons
+    pub mouse_position: (u32, u32),
+    pub mouse_input: (bool, bool),
+
+    // current sample when accumulating
+    pub sample: u32,
+
+    // subpixel offset for the main image when accumulating, in pixels
+    pub jitter: [f32; 2],
+}
+
 // one iChannel and it's needed data
 pub enum Channel {
     // texture to use for this channel
     Texture(glium::Texture2d),
-    // buffer index to use
-    Buffer(usize),
+    // buffer index to use, and which of its attachments
+    Buffer(usize, usize),
     // or the keyboard texture
     Keyboard,
     // or nothing
@@ -20,145 +47,435 @@ pub enum Channel {
 }
 
 impl Channel {
+    // resolution of the channel, as in iChannelResolution
+    fn get_resolution(&self, buffers: &[Buffer]) -> [f32; 3] {
+        match self {
+            Channel::Texture(x) => resolution_uniform([x.width() as f32, x.height() as f32]),
+            Channel::Buffer(i, _) => resolution_uniform(buffers[*i].resolution),
+            _ => [0.0; 3],
+        }
+    }
+
     // select a texture for the channel
     fn get_texture<'a>(
         &'a self,
-        buffers: &'a [glium::Texture2d],
+        buffers: &'a [Buffer],
         empty: &'a glium::Texture2d,
     ) -> &'a glium::Texture2d {
         match self {
             Channel::Texture(x) => x,
-            Channel::Buffer(i) => &buffers[*i],
+            Channel::Buffer(i, attachment) => buffers[*i].attachment(*attachment),
             _ => empty,
         }
     }
 }
 
+/// how big a buffer is
+#[derive(Copy, Clone, PartialEq)]
+pub enum Size {
+    // relative to the window
+    Scaled(f32),
+    // always this many pixels
+    Fixed(u32, u32),
+}
+
+impl Default for Size {
+    fn default() -> Self {
+        Self::Scaled(1.0)
+    }
+}
+
+/// what a buffer stores its pixels as
+#[derive(Copy, Clone, PartialEq, Eq, Default)]
+pub enum Format {
+    Rgba8,
+    Rgba16F,
+    #[default]
+    Rgba32F,
+}
+
+impl std::str::FromStr for Format {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rgba8" => Ok(Self::Rgba8),
+            "rgba16f" => Ok(Self::Rgba16F),
+            "rgba32f" => Ok(Self::Rgba32F),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Format {
+    // format of the textures
+    fn texture_format(self) -> glium::texture::UncompressedFloatFormat {
+        match self {
+            Self::Rgba8 => glium::texture::UncompressedFloatFormat::U8U8U8U8,
+            Self::Rgba16F => glium::texture::UncompressedFloatFormat::F16F16F16F16,
+            Self::Rgba32F => glium::texture::UncompressedFloatFormat::F32F32F32F32,
+        }
+    }
+}
+
+/// what to do with the contents of a buffer when it's resized
+#[derive(Copy, Clone, PartialEq, Eq, Default)]
+pub enum Preserve {
+    // stretch them to the new size
+    #[default]
+    Scale,
+    // keep them at the same pixels, cutting off or padding with zeroes
+    Copy,
+    // throw them away
+    Clear,
+}
+
+impl std::str::FromStr for Preserve {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scale" => Ok(Self::Scale),
+            "copy" => Ok(Self::Copy),
+            "clear" => Ok(Self::Clear),
+            _ => Err(()),
+        }
+    }
+}
+
+/// how many attachments a buffer can have at most, the least opengl guarantees
+pub const MAX_ATTACHMENTS: usize = 8;
+
+/// how a buffer stores what it draws
+#[derive(Copy, Clone, PartialEq)]
+pub struct Storage {
+    // how big the buffer is, and what it stores
+    pub size: Size,
+    pub format: Format,
+
+    // what to do with the contents on resize
+    pub preserve: Preserve,
+
+    // how many outputs the shader has, each has its own texture
+    pub attachments: usize,
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self {
+            size: Size::default(),
+            format: Format::default(),
+            preserve: Preserve::default(),
+            attachments: 1,
+        }
+    }
+}
+
 pub struct Buffer {
+    // name of the pass, as used in the config
+    pub name: String,
+
+    // file name for every source number of the shader, for debug prints
+    pub sources: Vec<String>,
+
     // program of the buffer
     pub program: Option<glium::Program>,
 
     // buffers
     vertex_buffer: glium::VertexBuffer<Vert>,
 
-    // size
-    width: u32,
-    height: u32,
+    // how big the buffer is, what it stores, and how many outputs it has
+    storage: Storage,
+
+    // resolution the shader sees, this is not scaled by the render scale
+    pub resolution: [f32; 2],
+
+    // what the pass rendered last, and what it renders to next, one texture per attachment
+    // these are swapped after drawing, so passes can read their own previous frame
+    front: Vec<glium::Texture2d>,
+    back: Vec<glium::Texture2d>,
 
     // channels
     pub channels: [Channel; 4],
+
+    // measures how long the last draw took on the gpu, if timing
+    query: Option<glium::draw_parameters::TimeElapsedQuery>,
+
+    // how long the buffer took on the gpu when last measured, in milliseconds
+    pub gpu_time: Option<f32>,
 }
 
 impl Buffer {
+    /// make a buffer, resize has to be called before drawing to get the right size
     pub fn new(
         display: &glium::Display,
-        width: u32,
-        height: u32,
+        name: &str,
+        storage: Storage,
         program: Option<glium::Program>,
         channels: [Channel; 4],
-    ) -> (Self, glium::Texture2d) {
-        (
-            Self {
-                program,
-                channels,
-                width,
-                height,
-                vertex_buffer: glium::VertexBuffer::new(
-                    display,
-                    &[
-                        Vert { pos: [-1.0, -1.0] },
-                        Vert { pos: [3.0, -1.0] },
-                        Vert { pos: [-1.0, 3.0] },
-                    ],
-                )
-                .expect("failed to make vertex buffer"),
-            },
-            glium::Texture2d::empty_with_format(
+        sources: Vec<String>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            sources,
+            program,
+            channels,
+            query: None,
+            gpu_time: None,
+            storage,
+            resolution: [1.0, 1.0],
+            front: (0..storage.attachments)
+                .map(|_| Self::texture(display, storage.format, 1, 1))
+                .collect(),
+            back: (0..storage.attachments)
+                .map(|_| Self::texture(display, storage.format, 1, 1))
+                .collect(),
+            vertex_buffer: glium::VertexBuffer::new(
                 display,
-                glium::texture::UncompressedFloatFormat::F32F32F32F32,
-                glium::texture::MipmapsOption::AutoGeneratedMipmaps,
-                width,
-                height,
+                &[
+                    Vert { pos: [-1.0, -1.0] },
+                    Vert { pos: [3.0, -1.0] },
+                    Vert { pos: [-1.0, 3.0] },
+                ],
             )
-            .expect("failed to make texture"),
+            .expect("failed to make vertex buffer"),
+        }
+    }
+
+    // makes a texture to render to, cleared to zero
+    fn texture(
+        display: &glium::Display,
+        format: Format,
+        width: u32,
+        height: u32,
+    ) -> glium::Texture2d {
+        let texture = glium::Texture2d::empty_with_format(
+            display,
+            format.texture_format(),
+            glium::texture::MipmapsOption::AutoGeneratedMipmaps,
+            width,
+            height,
         )
+        .expect("failed to make texture");
+        texture.as_surface().clear_color(0.0, 0.0, 0.0, 0.0);
+        texture
     }
 
-    pub fn draw(
-        &mut self,
+    /// what the pass rendered last, to the first attachment
+    pub fn output(&self) -> &glium::Texture2d {
+        &self.front[0]
+    }
+
+    /// what the pass rendered last, to the given attachment
+    pub fn attachment(&self, attachment: usize) -> &glium::Texture2d {
+        &self.front[attachment]
+    }
+
+    /// how many outputs the pass has
+    pub fn attachments(&self) -> usize {
+        self.storage.attachments
+    }
+
+    /// remake the textures if the size changed, for the given window size and render scale
+    pub fn resize(&mut self, display: &glium::Display, window: (u32, u32), scale: f32) {
+        let (width, height) = match self.storage.size {
+            Size::Scaled(x) => {
+                self.resolution = [window.0 as f32 * x, window.1 as f32 * x];
+                (
+                    ((window.0 as f32 * x * scale) as u32).max(1),
+                    ((window.1 as f32 * x * scale) as u32).max(1),
+                )
+            }
+            Size::Fixed(width, height) => {
+                self.resolution = [width as f32, height as f32];
+                (width, height)
+            }
+        };
+
+        // fixed size buffers never get here, so they're never cleared
+        if self.front[0].dimensions() != (width, height) {
+            // only the front has to be kept, the back is drawn over before it's used
+            self.front = self
+                .front
+                .iter()
+                .map(|old| self.resized(display, old, width, height))
+                .collect();
+            self.back = (0..self.storage.attachments)
+                .map(|_| Self::texture(display, self.storage.format, width, height))
+                .collect();
+        }
+    }
+
+    // new texture of the given size, with the contents of the old one kept as set by preserve
+    fn resized(
+        &self,
         display: &glium::Display,
-        index: usize,
-        backbuffers: &mut [glium::Texture2d; 5],
-        time: f32,
-        frame: i32,
-        mouse_position: (u32, u32),
-        mouse_input: (bool, bool),
-        scale: f32,
-        empty: &glium::Texture2d,
-    ) {
-        // get the image size
-        let resolution = display.get_framebuffer_dimensions();
-
-        // remake framebuffers if needed
-        if (
-            (resolution.0 as f32 * scale) as u32,
-            (resolution.1 as f32 * scale) as u32,
-        ) != (
-            (self.width as f32 * scale) as u32,
-            (self.height as f32 * scale) as u32,
-        ) {
-            self.width = (resolution.0 as f32 * scale) as u32;
-            self.height = (resolution.1 as f32 * scale) as u32;
-
-            // TODO: preserve on resize
-            backbuffers[index] = glium::Texture2d::empty_with_format(
-                display,
-                glium::texture::UncompressedFloatFormat::F32F32F32F32,
-                glium::texture::MipmapsOption::AutoGeneratedMipmaps,
-                self.width,
-                self.height,
-            )
-            .expect("failed to make texture");
+        old: &glium::Texture2d,
+        width: u32,
+        height: u32,
+    ) -> glium::Texture2d {
+        let texture = Self::texture(display, self.storage.format, width, height);
+        match self.storage.preserve {
+            Preserve::Scale => old
+                .as_surface()
+                .fill(&texture.as_surface(), MagnifySamplerFilter::Linear),
+            // from the bottom left, as that's where fragCoord starts
+            Preserve::Copy => {
+                let (width, height) = (old.width().min(width), old.height().min(height));
+                old.as_surface().blit_color(
+                    &glium::Rect {
+                        left: 0,
+                        bottom: 0,
+                        width,
+                        height,
+                    },
+                    &texture.as_surface(),
+                    &glium::BlitTarget {
+                        left: 0,
+                        bottom: 0,
+                        width: width as i32,
+                        height: height as i32,
+                    },
+                    MagnifySamplerFilter::Nearest,
+                );
+            }
+            Preserve::Clear => (),
         }
+        texture
+    }
 
-        // make the targets, to the backbuffer, so swapping is easier
-        let mut target = backbuffers[index].as_surface();
+    /// measure how long the next draw takes on the gpu
+    /// does nothing if timer queries are not supported
+    pub fn start_timing(&mut self, display: &glium::Display) {
+        self.query = glium::draw_parameters::TimeElapsedQuery::new(display).ok();
+    }
 
-        // make the uniforms and inputs
-        let uniforms = glium::uniform! {
+    /// get how long the last timed draw took on the gpu
+    /// waits for the gpu to finish if needed
+    pub fn update_gpu_time(&mut self) {
+        if let Some(query) = self.query.take() {
+            self.gpu_time = Some(query.get() as f32 / 1_000_000.0);
+        }
+    }
+
+    // inputs for the shader, with the buffers to use for the channels
+    // only runs the given fragCoord if debug printing
+    fn uniforms<'a>(
+        &'a self,
+        buffers: &'a [Buffer],
+        info: &FrameInfo,
+        empty: &'a glium::Texture2d,
+        debug: Option<[f32; 2]>,
+    ) -> impl glium::uniforms::Uniforms + 'a {
+        glium::uniform! {
 
             // resolution
-            iResolution: [resolution.0 as f32, resolution.1 as f32, resolution.1 as f32 / resolution.0 as f32],
+            iResolution: resolution_uniform(self.resolution),
 
             // frame counter
-            iFrame: frame as i32,
+            iFrame: info.frame,
 
             // time
-            iTime: time as f32,
+            iTime: info.time,
+            iTimeDelta: info.time_delta,
+            iFrameRate: info.frame_rate,
 
             // mouse position and buttons
-            iMouse: [mouse_position.0 as f32, mouse_position.1 as f32, if mouse_input.0 { 1.0 } else { 0.0 }, if mouse_input.1 { 1.0 } else { 0.0 }],
+            iMouse: [info.mouse_position.0 as f32, info.mouse_position.1 as f32, if info.mouse_input.0 { 1.0 } else { 0.0 }, if info.mouse_input.1 { 1.0 } else { 0.0 }],
+
+            // accumulation sample, and the offset to go with it
+            iSampleIndex: info.sample as i32,
+            _internal_jitter: info.jitter,
 
             // user defined inputs
-            iChannel0: self.channels[0].get_texture(&backbuffers[1..], empty),
-            iChannel1: self.channels[1].get_texture(&backbuffers[1..], empty),
-            iChannel2: self.channels[2].get_texture(&backbuffers[1..], empty),
-            iChannel3: self.channels[3].get_texture(&backbuffers[1..], empty),
-        };
+            iChannel0: self.channels[0].get_texture(buffers, empty),
+            iChannel1: self.channels[1].get_texture(buffers, empty),
+            iChannel2: self.channels[2].get_texture(buffers, empty),
+            iChannel3: self.channels[3].get_texture(buffers, empty),
 
-        // only draw if the program is valid
+            // only run the given fragCoord if debug printing
+            _internal_debug: debug.is_some(),
+            _internal_debug_coord: debug.unwrap_or_default(),
+        }
+        // arrays are set per element
+        .add("iChannelResolution[0]", self.channels[0].get_resolution(buffers))
+        .add("iChannelResolution[1]", self.channels[1].get_resolution(buffers))
+        .add("iChannelResolution[2]", self.channels[2].get_resolution(buffers))
+        .add("iChannelResolution[3]", self.channels[3].get_resolution(buffers))
+    }
+
+    /// run the shader at a single fragCoord, and write what it printed to the target
+    pub fn debug_print(
+        &self,
+        buffers: &[Buffer],
+        info: &FrameInfo,
+        empty: &glium::Texture2d,
+        frag_coord: [f32; 2],
+        target: &glium::Texture2d,
+    ) {
         if let Some(prog) = &self.program {
+            let uniforms = self.uniforms(buffers, info, empty, Some(frag_coord));
             target
+                .as_surface()
                 .draw(
                     &self.vertex_buffer,
                     glium::index::NoIndices(glium::index::PrimitiveType::TriangleStrip),
-                    &prog,
+                    prog,
                     &uniforms,
                     &Default::default(),
                 )
                 .unwrap();
+        }
+    }
+
+    /// draw to the back textures, with the given buffers as inputs
+    /// swap has to be called after to make the result the output
+    pub fn draw(
+        &self,
+        display: &glium::Display,
+        buffers: &[Buffer],
+        info: &FrameInfo,
+        empty: &glium::Texture2d,
+    ) {
+        // make the uniforms and inputs
+        let uniforms = self.uniforms(buffers, info, empty, None);
+
+        // every attachment goes to the output with the same number in the shader
+        let names = (0..self.storage.attachments)
+            .map(|i| format!("_internal_fragcol{}", i))
+            .collect::<Vec<String>>();
+        let mut target = glium::framebuffer::MultiOutputFrameBuffer::new(
+            display,
+            names.iter().map(String::as_str).zip(&self.back),
+        )
+        .expect("failed to make framebuffer");
+
+        // only draw if the program is valid
+        if let Some(prog) = &self.program {
+            target
+                .draw(
+                    &self.vertex_buffer,
+                    glium::index::NoIndices(glium::index::PrimitiveType::TriangleStrip),
+                    prog,
+                    &uniforms,
+                    &glium::DrawParameters {
+                        time_elapsed_query: self.query.as_ref(),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
         } else {
             target.clear_color(0.0, 0.0, 0.0, 0.0);
         }
     }
+
+    /// make what was just drawn the output
+    pub fn swap(&mut self) {
+        std::mem::swap(&mut self.front, &mut self.back);
+    }
+}
+
+// resolution as given to the shader, with the aspect ratio (y / x) as z
+fn resolution_uniform(resolution: [f32; 2]) -> [f32; 3] {
+    [resolution[0], resolution[1], resolution[1] / resolution[0]]
 }
@@ -0,0 +1,141 @@
+use glium::backend::Facade;
+use glium::program::Binary;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::program::ProgramSource;
+
+/// most bytes of binaries to keep, the least recently used are removed after this
+pub const MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+/// compiled program binaries, kept on disk so shaders load quicker the next time
+#[derive(Clone)]
+pub struct Cache {
+    // directory the binaries are in
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// the cache in the user's cache directory, None if there isn't one
+    pub fn new() -> Option<Self> {
+        Some(Self {
+            dir: cache_dir()?.join("shadercrab"),
+        })
+    }
+
+    /// the cached binary of a program, if there is one
+    pub fn load<F: Facade>(&self, facade: &F, source: &ProgramSource) -> Option<Binary> {
+        let path = self.path(facade, source);
+        let data = std::fs::read(&path).ok()?;
+        if data.len() < 4 {
+            return None;
+        }
+
+        // keep track of when it was last used, for eviction
+        if let Ok(file) = std::fs::File::options().write(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+
+        // the format comes first
+        let (format, content) = data.split_at(4);
+        Some(Binary {
+            format: u32::from_le_bytes(format.try_into().ok()?),
+            content: content.to_vec(),
+        })
+    }
+
+    /// keep the binary of a program, and remove old ones if the cache is too big
+    pub fn store<F: Facade>(&self, facade: &F, source: &ProgramSource, binary: &Binary) {
+        if std::fs::create_dir_all(&self.dir).is_err() {
+            return;
+        }
+
+        let mut data = binary.format.to_le_bytes().to_vec();
+        data.extend_from_slice(&binary.content);
+
+        // written next to it first, so other instances never read half a binary
+        // with the process id in the name, so instances storing the same binary don't write to the same file
+        let path = self.path(facade, source);
+        let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+        if std::fs::write(&temporary, data).is_ok() {
+            let _ = std::fs::rename(&temporary, &path);
+        }
+
+        self.evict();
+    }
+
+    /// remove the cached binary of a program, for when it doesn't load anymore
+    pub fn remove<F: Facade>(&self, facade: &F, source: &ProgramSource) {
+        let _ = std::fs::remove_file(self.path(facade, source));
+    }
+
+    // remove the least recently used binaries until the cache fits
+    fn evict(&self) {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(x) => x,
+            Err(_) => return,
+        };
+
+        let mut files = entries
+            .filter_map(|x| {
+                let entry = x.ok()?;
+                let metadata = entry.metadata().ok()?;
+                Some((entry.path(), metadata.len(), metadata.modified().ok()?))
+            })
+            .filter(|(path, _, _)| path.extension().is_some_and(|x| x == "bin"))
+            .collect::<Vec<(PathBuf, u64, SystemTime)>>();
+
+        // newest first, so the ones past the size are the oldest
+        files.sort_by_key(|x| std::cmp::Reverse(x.2));
+        let mut size = 0;
+        for (path, length, _) in files {
+            size += length;
+            if size > MAX_SIZE {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    // where the binary of a program is
+    // the same source can compile differently on another gpu or driver, so those are part of the key
+    // the types of the samplers are declared in the fragment source, so they're part of it as well
+    fn path<F: Facade>(&self, facade: &F, source: &ProgramSource) -> PathBuf {
+        let context = facade.get_context();
+        let key = hash(&[
+            context.get_opengl_renderer_string(),
+            context.get_opengl_version_string(),
+            &source.vertex,
+            &source.fragment,
+        ]);
+        self.dir.join(format!("{:016x}.bin", key))
+    }
+}
+
+// fnv-1a of all parts, with a zero byte between them so they can't run into each other
+fn hash(parts: &[&str]) -> u64 {
+    let mut hash = 0xcbf29ce484222325_u64;
+    for byte in parts
+        .iter()
+        .flat_map(|x| x.bytes().chain(std::iter::once(0)))
+    {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+// where caches go on this platform
+fn cache_dir() -> Option<PathBuf> {
+    let var = |name: &str| {
+        std::env::var_os(name)
+            .filter(|x| !x.is_empty())
+            .map(PathBuf::from)
+    };
+    if cfg!(target_os = "windows") {
+        var("LOCALAPPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|x| x.join("Library").join("Caches"))
+    } else {
+        var("XDG_CACHE_HOME").or_else(|| var("HOME").map(|x| x.join(".cache")))
+    }
+}
@@ -0,0 +1,59 @@
+use glslang::ShaderStage;
+
+use crate::args::MessageFormat;
+use crate::diagnostic::{parse_log, Diagnostic, Severity};
+use crate::parser::Shadertoy;
+use crate::program::validate;
+
+/// check that every pass compiles, without needing a gpu
+/// errors are printed with the file and line they're on, returns whether there were none
+pub fn check(shadertoy: &Shadertoy, format: MessageFormat) -> bool {
+    let diagnostics = diagnostics(shadertoy);
+    for diagnostic in &diagnostics {
+        diagnostic.print(format);
+    }
+    let errors = diagnostics
+        .iter()
+        .filter(|x| x.severity == Severity::Error)
+        .count();
+
+    // json output only has the diagnostics
+    if format == MessageFormat::Json {
+        return errors == 0;
+    }
+
+    // a project that failed to load has no passes to check
+    let passes = match shadertoy.failed_to_load() {
+        true => 0,
+        false => shadertoy.program_sources().len(),
+    };
+    println!("Checked {} passes, found {} errors", passes, errors);
+    errors == 0
+}
+
+/// what's wrong with the files, and the errors in every pass, without printing them
+pub fn diagnostics(shadertoy: &Shadertoy) -> Vec<Diagnostic> {
+    let mut diagnostics = shadertoy.errors().to_vec();
+
+    // a project that failed to load only has an empty pass, which would only give a confusing error
+    if shadertoy.failed_to_load() {
+        return diagnostics;
+    }
+
+    // the same error in common or an include would show up once for every pass
+    for (_, source) in shadertoy.program_sources() {
+        let log = match validate(&source.fragment, ShaderStage::Fragment) {
+            Ok(()) => continue,
+            Err(log) => log,
+        };
+        for diagnostic in parse_log(&log, &source.names, &source.pass) {
+            if !diagnostics
+                .iter()
+                .any(|x| x.to_string() == diagnostic.to_string())
+            {
+                diagnostics.push(diagnostic);
+            }
+        }
+    }
+    diagnostics
+}
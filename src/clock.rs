@@ -0,0 +1,149 @@
+use std::time::Instant;
+
+// how long a single frame step is, in seconds, when not using a fixed timestep
+const STEP_TIME: f32 = 1.0 / 60.0;
+
+/// keeps track of iTime and iFrame, and allows pausing, stepping and changing the speed
+pub struct Clock {
+    // current time, in seconds
+    time: f32,
+
+    // time between the last two frames, in seconds
+    delta: f32,
+
+    // current frame
+    frame: i32,
+
+    // time to go back to when reset
+    start: f32,
+
+    // when time was last updated
+    last_update: Instant,
+
+    // average real time between ticks, in seconds
+    frame_time: f32,
+
+    // whether time and frames are stopped
+    pub paused: bool,
+
+    // how fast time goes, 1 is realtime
+    speed: f32,
+
+    // whether a single frame should be done, even when paused
+    step: bool,
+
+    // frames per second if time is computed from the frame instead of measured
+    fixed: Option<f32>,
+
+    // time and frame that a fixed timestep counts from
+    // changed when the speed changes or time is skipped, so that time stays continuous
+    fixed_base: (f32, i32),
+}
+
+impl Clock {
+    pub fn new(start: f32, speed: f32, paused: bool, fixed: Option<f32>) -> Self {
+        Self {
+            time: start,
+            delta: fixed.map_or(0.0, |fps| speed / fps),
+            frame: 0,
+            start,
+            last_update: Instant::now(),
+            frame_time: STEP_TIME,
+            paused,
+            speed,
+            step: false,
+            fixed,
+            fixed_base: (start, 0),
+        }
+    }
+
+    /// current time, in seconds
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// time between the last two frames, in seconds
+    pub fn delta(&self) -> f32 {
+        self.delta
+    }
+
+    /// frames per second, measured unless using a fixed timestep
+    pub fn frame_rate(&self) -> f32 {
+        self.fixed.unwrap_or(1.0 / self.frame_time)
+    }
+
+    /// current frame
+    pub fn frame(&self) -> i32 {
+        self.frame
+    }
+
+    /// how fast time goes
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// go back to the start time and the first frame
+    pub fn reset(&mut self) {
+        self.time = self.start;
+        self.frame = 0;
+        self.fixed_base = (self.start, 0);
+        self.last_update = Instant::now();
+    }
+
+    /// move time forward to now, and go to the next frame
+    /// returns false if nothing changed because we're paused
+    pub fn tick(&mut self) -> bool {
+        let elapsed = self.last_update.elapsed().as_secs_f32();
+        self.last_update = Instant::now();
+
+        // smooth it out a bit, so it's readable
+        self.frame_time = self.frame_time * 0.9 + elapsed * 0.1;
+
+        // don't do anything if paused, unless we need to do a single step
+        if self.paused && !self.step {
+            return false;
+        }
+
+        self.frame += 1;
+
+        if let Some(fps) = self.fixed {
+            // time only depends on the frame, so it's the same on every machine
+            let (time, frame) = self.fixed_base;
+            self.time = time + (self.frame - frame) as f32 * self.speed / fps;
+            self.delta = self.speed / fps;
+        } else if self.step {
+            // single step, as if one frame passed
+            self.delta = STEP_TIME * self.speed;
+            self.time += self.delta;
+        } else {
+            self.delta = elapsed * self.speed;
+            self.time += self.delta;
+        }
+
+        self.step = false;
+        true
+    }
+
+    /// pause or unpause
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// do a single frame on the next tick, and pause after
+    pub fn step(&mut self) {
+        self.paused = true;
+        self.step = true;
+    }
+
+    /// jump forward or backward in time, without changing the frame
+    pub fn skip(&mut self, seconds: f32) {
+        self.time += seconds;
+        self.fixed_base = (self.time, self.frame);
+    }
+
+    /// change how fast time goes
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+        self.fixed_base = (self.time, self.frame);
+    }
+}
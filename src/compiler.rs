@@ -0,0 +1,255 @@
+use glium::glutin;
+use glium::program::Binary;
+use glium::Program;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::args::MessageFormat;
+use crate::cache::Cache;
+use crate::diagnostic::report;
+use crate::program::{compile, load_binary, load_program, ProgramSource, Version};
+
+/// programs from a finished job, to swap in all at once
+pub enum Finished {
+    // every pass, in order, as they were when reloading
+    Reload(Vec<Program>),
+    // some passes, with their index
+    Recompile(Vec<(usize, Program)>),
+    // a pass didn't compile, so none are swapped in, with the index of every pass in the job
+    Failed { passes: Vec<usize>, reload: bool },
+}
+
+/// the programs, if every one compiled, as the passes of a job only change together
+pub fn all_compiled(programs: Vec<(usize, Option<Program>)>) -> Option<Vec<(usize, Program)>> {
+    programs
+        .into_iter()
+        .map(|(i, program)| Some((i, program?)))
+        .collect()
+}
+
+// passes to compile together
+struct Job {
+    // counts up with every job
+    generation: u64,
+
+    // whether it's every pass after reloading, which makes all jobs before it useless
+    reload: bool,
+
+    // index and source of every pass
+    sources: Vec<(usize, ProgramSource)>,
+}
+
+// what the program of a pass compiled to
+enum Compiled {
+    // with the source, in case the binary doesn't load
+    Binary(Binary, ProgramSource),
+    // it compiled, but there's no binary, so it has to be compiled again where it's used
+    Source(ProgramSource),
+    Failed,
+}
+
+// what a job compiled to
+struct Done {
+    generation: u64,
+    reload: bool,
+
+    // index of every pass, and its program
+    programs: Vec<(usize, Compiled)>,
+}
+
+/// compiles programs on another thread, with its own context, so the window keeps going
+/// the programs are sent back as binaries, which are quick to load
+pub struct Compiler {
+    jobs: Sender<Job>,
+    done: Receiver<Done>,
+
+    // generation of the last job sent, and of the last reload
+    generation: u64,
+    reload: u64,
+
+    // how to print compile errors, for programs that have to be compiled here after all
+    format: MessageFormat,
+
+    // where to keep compiled programs, if anywhere
+    cache: Option<Cache>,
+}
+
+impl Compiler {
+    /// start the thread, None if there's no context for it, or the driver can't give binaries
+    pub fn new<T>(
+        event_loop: &glutin::event_loop::EventLoopWindowTarget<T>,
+        format: MessageFormat,
+        cache: Option<Cache>,
+    ) -> Option<Self> {
+        let context = glutin::ContextBuilder::new()
+            .build_headless(event_loop, glutin::dpi::PhysicalSize::new(1, 1))
+            .ok()?;
+
+        let (jobs, receive_jobs) = channel();
+        let (send_done, done) = channel();
+        let (send_ready, ready) = channel();
+        let worker_cache = cache.clone();
+        std::thread::spawn(move || {
+            let facade = match glium::HeadlessRenderer::new(context) {
+                Ok(x) => x,
+                Err(_) => return,
+            };
+
+            // binaries are needed to get the programs back
+            let _ = send_ready.send(supports_binaries(&facade));
+            work(&facade, receive_jobs, send_done, format, worker_cache);
+        });
+
+        // the thread quits without saying it's ready if there's no context
+        match ready.recv() {
+            Ok(true) => Some(Self {
+                jobs,
+                done,
+                generation: 0,
+                reload: 0,
+                format,
+                cache,
+            }),
+            _ => None,
+        }
+    }
+
+    /// compile the programs of the given passes, and reload them all if asked
+    pub fn compile(&mut self, sources: Vec<(usize, ProgramSource)>, reload: bool) {
+        self.generation += 1;
+        if reload {
+            self.reload = self.generation;
+        }
+
+        let _ = self.jobs.send(Job {
+            generation: self.generation,
+            reload,
+            sources,
+        });
+    }
+
+    /// the programs of the next finished job, if there is one
+    /// jobs from before the last reload are skipped, as they're for passes that are gone
+    pub fn finished(&mut self, display: &glium::Display) -> Option<Finished> {
+        let done = self.done.try_iter().find(|x| x.generation >= self.reload)?;
+        let passes = done.programs.iter().map(|x| x.0).collect();
+        let failed = Finished::Failed {
+            passes,
+            reload: done.reload,
+        };
+        if done
+            .programs
+            .iter()
+            .any(|x| matches!(x.1, Compiled::Failed))
+        {
+            return Some(failed);
+        }
+
+        let programs = done
+            .programs
+            .into_iter()
+            .map(|(i, compiled)| {
+                let program = match compiled {
+                    Compiled::Binary(binary, source) => load_binary(display, binary)
+                        .or_else(|| compile(display, &source, self.format, self.cache.as_ref())),
+                    Compiled::Source(source) => {
+                        compile(display, &source, self.format, self.cache.as_ref())
+                    }
+                    Compiled::Failed => None,
+                };
+                (i, program)
+            })
+            .collect();
+
+        // it can still fail here, when the binary doesn't load and compiling it again doesn't work
+        let programs = match all_compiled(programs) {
+            Some(x) => x,
+            None => return Some(failed),
+        };
+        Some(match done.reload {
+            true => Finished::Reload(programs.into_iter().map(|x| x.1).collect()),
+            false => Finished::Recompile(programs),
+        })
+    }
+}
+
+// compile jobs until the window is gone
+fn work(
+    facade: &glium::HeadlessRenderer,
+    jobs: Receiver<Job>,
+    done: Sender<Done>,
+    format: MessageFormat,
+    cache: Option<Cache>,
+) {
+    while let Ok(job) = jobs.recv() {
+        // anything before the last reload is for passes that are gone
+        let mut pending = vec![job];
+        pending.extend(jobs.try_iter());
+        let start = pending.iter().rposition(|x| x.reload).unwrap_or(0);
+
+        for job in pending.drain(start..) {
+            let programs = job
+                .sources
+                .into_iter()
+                .map(|(i, source)| {
+                    // cached binaries don't need compiling, but are checked here first
+                    // ones that don't load anymore, such as after a driver update, are compiled again
+                    if let Some(cache) = &cache {
+                        if let Some(binary) = cache.load(facade, &source) {
+                            let copy = Binary {
+                                format: binary.format,
+                                content: binary.content.clone(),
+                            };
+                            if load_binary(facade, copy).is_some() {
+                                return (i, Compiled::Binary(binary, source));
+                            }
+                            cache.remove(facade, &source);
+                        }
+                    }
+
+                    let compiled = match load_program(facade, &source) {
+                        Ok(program) => match program.get_binary() {
+                            Ok(binary) => {
+                                if let Some(cache) = &cache {
+                                    cache.store(facade, &source, &binary);
+                                }
+                                Compiled::Binary(binary, source)
+                            }
+                            Err(_) => Compiled::Source(source),
+                        },
+                        Err(log) => {
+                            report(&log, &source.names, &source.pass, format);
+                            Compiled::Failed
+                        }
+                    };
+                    (i, compiled)
+                })
+                .collect();
+
+            let result = Done {
+                generation: job.generation,
+                reload: job.reload,
+                programs,
+            };
+            if done.send(result).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+// whether the driver gives binaries of programs, by compiling one
+fn supports_binaries(facade: &glium::HeadlessRenderer) -> bool {
+    let source = ProgramSource::new(
+        "",
+        Vec::new(),
+        "",
+        "void mainImage(out vec4 fragColor, in vec2 fragCoord) { fragColor = vec4(0.0); }",
+        1,
+        &[],
+        Version::default(),
+    );
+    load_program(facade, &source)
+        .ok()
+        .and_then(|x| x.get_binary().ok())
+        .is_some()
+}
@@ -0,0 +1,214 @@
+use glium::Surface;
+
+use crate::buffer::Vert;
+
+/// what to show in the window
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum View {
+    // the main image
+    Image,
+    // all passes next to each other
+    Tiles,
+    // a single pass, full screen
+    Solo,
+}
+
+impl View {
+    /// the view after this one
+    pub fn next(self) -> Self {
+        match self {
+            Self::Image => Self::Tiles,
+            Self::Tiles => Self::Solo,
+            Self::Solo => Self::Image,
+        }
+    }
+}
+
+/// how to change values before showing them
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Remap {
+    // as is
+    Off,
+    // map the range to 0 to 1
+    Range,
+    // map the range to 0 to 1, and show values outside of it with a false-colour ramp
+    FalseColour,
+}
+
+impl Remap {
+    /// the remap after this one
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Range,
+            Self::Range => Self::FalseColour,
+            Self::FalseColour => Self::Off,
+        }
+    }
+}
+
+/// shows passes other than the main image, for debugging
+pub struct DebugView {
+    // what to show
+    pub view: View,
+
+    // pass to show when solo, and to inspect outside of the tiles
+    pub pass: usize,
+
+    // how to change the values
+    pub remap: Remap,
+
+    // values that are mapped to 0 and 1
+    pub range: (f32, f32),
+
+    // program to draw a pass with
+    program: glium::Program,
+}
+
+impl DebugView {
+    pub fn new(display: &glium::Display) -> Self {
+        // outputs_srgb, same as the main image, so values are shown as the shader wrote them
+        let program = glium::program::Program::new(
+            display,
+            glium::program::ProgramCreationInput::SourceCode {
+                vertex_shader: "
+            #version 140
+
+            in vec2 pos;
+            out vec2 vpos;
+
+            void main() {
+                gl_Position = vec4(pos, 0.0, 1.0);
+                vpos = pos * 0.5 + 0.5;
+            }
+            ",
+                tessellation_control_shader: None,
+                tessellation_evaluation_shader: None,
+                geometry_shader: None,
+                fragment_shader: "
+            #version 140
+
+            in vec2 vpos;
+            out vec4 fragcol;
+
+            uniform sampler2D image;
+
+            // 0 is off, 1 is range, 2 is false colour
+            uniform int remap;
+
+            // values mapped to 0 and 1
+            uniform vec2 range;
+
+            void main() {
+                vec4 value = texture(image, vpos);
+                vec3 mapped = (value.rgb - range.x) / (range.y - range.x);
+
+                if (remap == 0) {
+                    fragcol = value;
+                } else if (remap == 1) {
+                    fragcol = vec4(mapped, 1.0);
+                } else {
+                    float low = min(mapped.r, min(mapped.g, mapped.b));
+                    float high = max(mapped.r, max(mapped.g, mapped.b));
+
+                    // below the range goes from dark to light blue, above from red to yellow
+                    // the further outside the range, the lighter
+                    if (low < 0.0) {
+                        fragcol = vec4(mix(vec3(0.0, 0.1, 0.5), vec3(0.3, 0.9, 1.0), clamp(-low, 0.0, 1.0)), 1.0);
+                    } else if (high > 1.0) {
+                        fragcol = vec4(mix(vec3(0.6, 0.0, 0.0), vec3(1.0, 1.0, 0.3), clamp(high - 1.0, 0.0, 1.0)), 1.0);
+                    } else {
+                        fragcol = vec4(mapped, 1.0);
+                    }
+                }
+            }
+            ",
+                transform_feedback_varyings: None,
+                outputs_srgb: true,
+                uses_point_size: false,
+            },
+        )
+        .expect("failed to make debug view program");
+
+        Self {
+            view: View::Image,
+            pass: 0,
+            remap: Remap::Off,
+            range: (0.0, 1.0),
+            program,
+        }
+    }
+
+    /// where each of the given number of passes is shown, as left, top, width and height in window pixels
+    pub fn rects(&self, passes: usize, window: (u32, u32)) -> Vec<[f32; 4]> {
+        let (width, height) = (window.0 as f32, window.1 as f32);
+        match self.view {
+            View::Image | View::Solo => vec![[0.0, 0.0, width, height]; passes],
+            View::Tiles => {
+                // as square as possible
+                let columns = (passes as f32).sqrt().ceil().max(1.0) as usize;
+                let rows = passes.div_ceil(columns);
+                let (tile_width, tile_height) = (width / columns as f32, height / rows as f32);
+
+                // keep the aspect ratio of the window
+                let scale = (tile_width / width).min(tile_height / height);
+                let size = [width * scale, height * scale];
+
+                (0..passes)
+                    .map(|i| {
+                        [
+                            (i % columns) as f32 * tile_width + (tile_width - size[0]) * 0.5,
+                            (i / columns) as f32 * tile_height + (tile_height - size[1]) * 0.5,
+                            size[0],
+                            size[1],
+                        ]
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// draw a texture in the given rect, as left, top, width and height in window pixels
+    pub fn draw<S: Surface>(
+        &self,
+        target: &mut S,
+        vertex_buffer: &glium::VertexBuffer<Vert>,
+        texture: &glium::Texture2d,
+        rect: [f32; 4],
+    ) {
+        let uniforms = glium::uniform! {
+            // nearest, so single pixels are visible when the pass is small
+            image: texture
+                .sampled()
+                .wrap_function(glium::uniforms::SamplerWrapFunction::Clamp)
+                .minify_filter(glium::uniforms::MinifySamplerFilter::Linear)
+                .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest),
+            remap: self.remap as i32,
+            range: [self.range.0, self.range.1],
+        };
+
+        target
+            .draw(
+                vertex_buffer,
+                glium::index::NoIndices(glium::index::PrimitiveType::TriangleStrip),
+                &self.program,
+                &uniforms,
+                &glium::DrawParameters {
+                    viewport: Some(viewport(target, rect)),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+    }
+}
+
+/// viewport that covers the given rect, as left, top, width and height in window pixels
+pub fn viewport<S: Surface>(target: &S, rect: [f32; 4]) -> glium::Rect {
+    // the viewport goes from the bottom left
+    let (_, height) = target.get_dimensions();
+    glium::Rect {
+        left: rect[0] as u32,
+        bottom: (height as f32 - rect[1] - rect[3]).max(0.0) as u32,
+        width: rect[2] as u32,
+        height: rect[3] as u32,
+    }
+}
@@ -0,0 +1,78 @@
+use crate::args::MessageFormat;
+use crate::buffer::Buffer;
+use crate::diagnostic::message;
+
+// how many prints of a single pass are kept
+const MAX_PRINTS: u32 = 32;
+
+/// reads back values printed with debugPrint in the shader
+pub struct DebugPrinter {
+    // where to print on the next frame, in window pixels from the top left
+    pub request: Option<(f64, f64)>,
+
+    // two pixels per print, the value and where it came from
+    target: glium::Texture2d,
+}
+
+impl DebugPrinter {
+    pub fn new(display: &glium::Display) -> Self {
+        Self {
+            request: None,
+            target: glium::Texture2d::empty_with_format(
+                display,
+                glium::texture::UncompressedFloatFormat::F32F32F32F32,
+                glium::texture::MipmapsOption::NoMipmap,
+                MAX_PRINTS * 2,
+                1,
+            )
+            .expect("failed to make texture"),
+        }
+    }
+
+    /// where the shader writes its prints to
+    pub fn target(&self) -> &glium::Texture2d {
+        &self.target
+    }
+
+    /// read back the prints of a pass, and show them
+    pub fn report(&self, buffer: &Buffer, format: MessageFormat) {
+        let pass = &buffer.name;
+        let pixels: Vec<Vec<(f32, f32, f32, f32)>> = self
+            .target
+            .main_level()
+            .first_layer()
+            .into_image(None)
+            .expect("failed to get texture image")
+            .raw_read(&glium::Rect {
+                left: 0,
+                bottom: 0,
+                width: MAX_PRINTS * 2,
+                height: 1,
+            });
+
+        // every info pixel has the total
+        let total = pixels[0][1].3 as u32;
+        for print in pixels[0].chunks(2).take(total.min(MAX_PRINTS) as usize) {
+            let (x, y, z, w) = print[0];
+            let (line, source, components, _) = print[1];
+            let value = match components as u32 {
+                0 => format!("{}", x as i32),
+                1 => format!("{}", x),
+                2 => format!("({}, {})", x, y),
+                3 => format!("({}, {}, {})", x, y, z),
+                _ => format!("({}, {}, {}, {})", x, y, z, w),
+            };
+            let source = buffer
+                .sources
+                .get(source as usize)
+                .map_or("", String::as_str);
+            let text = format!("[{}] {}:{}: {}", pass, source, line as u32, value);
+            message(format, &text);
+        }
+
+        if total > MAX_PRINTS {
+            let text = format!("[{}] {} more prints not shown", pass, total - MAX_PRINTS);
+            message(format, &text);
+        }
+    }
+}
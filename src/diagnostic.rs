@@ -0,0 +1,369 @@
+use serde::Serialize;
+
+use crate::args::MessageFormat;
+use crate::preprocess::name_sources;
+
+/// how bad a diagnostic is
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl std::str::FromStr for Severity {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "error" => Ok(Self::Error),
+            "warning" => Ok(Self::Warning),
+            _ => Err(()),
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// an error or warning from loading, compiling or checking a pass
+#[derive(Clone, Serialize)]
+pub struct Diagnostic {
+    // pass it was found in, if it's in one
+    pub pass: Option<String>,
+
+    // file it's in, relative to the config, if it's in a file
+    pub file: Option<String>,
+
+    // line and column in the file, counting from 1, if known
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match (&self.file, self.line, self.column) {
+            (Some(file), Some(line), Some(column)) => write!(f, "{}:{}:{}", file, line, column)?,
+            (Some(file), Some(line), None) => write!(f, "{}:{}", file, line)?,
+            (Some(file), None, _) => write!(f, "{}", file)?,
+            (None, ..) => match &self.pass {
+                Some(pass) => write!(f, "pass {}", pass)?,
+                None => return write!(f, "{}: {}", self.severity, self.message),
+            },
+        }
+        write!(f, ": {}: {}", self.severity, self.message)
+    }
+}
+
+impl Diagnostic {
+    /// about a file, at the line if known, instead of a pass
+    pub fn in_file(file: String, line: Option<usize>, severity: Severity, message: String) -> Self {
+        Self {
+            pass: None,
+            file: Some(file),
+            line,
+            column: None,
+            severity,
+            message,
+        }
+    }
+
+    /// print it as a line of text, or a line of json
+    pub fn print(&self, format: MessageFormat) {
+        match format {
+            MessageFormat::Human => println!("{}", self),
+            MessageFormat::Json => println!(
+                "{}",
+                serde_json::to_string(self).expect("Failed to serialize diagnostic")
+            ),
+        }
+    }
+}
+
+/// print text that isn't a diagnostic, to stderr with json, so stdout only has diagnostics
+pub fn message(format: MessageFormat, text: &str) {
+    match format {
+        MessageFormat::Human => println!("{}", text),
+        MessageFormat::Json => eprintln!("{}", text),
+    }
+}
+
+/// print the log of a pass that failed to compile
+/// names has the name of the file for every source number
+pub fn report(log: &str, names: &[String], pass: &str, format: MessageFormat) {
+    match format {
+        MessageFormat::Human => println!("Failed to compile shader:\n{}", name_sources(log, names)),
+        MessageFormat::Json => {
+            for diagnostic in parse_log(log, names, pass) {
+                diagnostic.print(format);
+            }
+        }
+    }
+}
+
+/// parse a compiler log into diagnostics, for the formats drivers use
+/// lines that aren't in any of them are added to the message of the diagnostic before it
+pub fn parse_log(log: &str, names: &[String], pass: &str) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    for text in log.lines().map(str::trim).filter(|x| !x.is_empty()) {
+        // amd ends with the amount of errors, and says where it stopped after the error that stopped it
+        // neither is an error itself
+        if text.ends_with("No code generated.") || text.ends_with("compilation terminated") {
+            continue;
+        }
+
+        let parsed = mesa(text).or_else(|| nvidia(text)).or_else(|| amd(text));
+        match (parsed, diagnostics.last_mut()) {
+            (Some((source, line, column, severity, message)), _) => {
+                // source 0 is the code shadercrab adds, which isn't in a file
+                let file = names.get(source).filter(|x| source > 0 && !x.is_empty());
+                diagnostics.push(Diagnostic {
+                    pass: Some(pass.to_string()),
+                    file: file.cloned(),
+                    line: file.map(|_| line),
+                    column: column.filter(|_| file.is_some()),
+                    severity,
+                    message: message.trim().to_string(),
+                });
+            }
+            (None, Some(last)) => {
+                last.message.push('\n');
+                last.message.push_str(text);
+            }
+            (None, None) => diagnostics.push(Diagnostic {
+                pass: Some(pass.to_string()),
+                file: None,
+                line: None,
+                column: None,
+                severity: Severity::Error,
+                message: text.to_string(),
+            }),
+        }
+    }
+    diagnostics
+}
+
+// source number, line, column if given, severity and message of a line in a log
+type Parsed<'a> = (usize, usize, Option<usize>, Severity, &'a str);
+
+// mesa, which intel and amd use on linux: 0:12(5): error: message
+fn mesa(text: &str) -> Option<Parsed<'_>> {
+    let (source, rest) = text.split_once(':')?;
+    let (line, rest) = rest.split_once('(')?;
+    let (column, rest) = rest.split_once("): ")?;
+    let (severity, message) = rest.split_once(": ")?;
+    Some((
+        source.parse().ok()?,
+        line.parse().ok()?,
+        column.parse().ok(),
+        severity.parse().ok()?,
+        message,
+    ))
+}
+
+// nvidia: 0(12) : error C1008: message
+fn nvidia(text: &str) -> Option<Parsed<'_>> {
+    let (source, rest) = text.split_once('(')?;
+    let (line, rest) = rest.split_once(") : ")?;
+    let (severity, message) = rest.split_once(' ')?;
+    Some((
+        source.parse().ok()?,
+        line.parse().ok()?,
+        None,
+        severity.parse().ok()?,
+        message,
+    ))
+}
+
+// amd and intel on windows, and apple: ERROR: 0:12: message
+fn amd(text: &str) -> Option<Parsed<'_>> {
+    let (severity, rest) = text.split_once(": ")?;
+    let (source, rest) = rest.split_once(':')?;
+    let (line, message) = rest.split_once(':')?;
+    Some((
+        source.trim().parse().ok()?,
+        line.parse().ok()?,
+        None,
+        severity.parse().ok()?,
+        message,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names() -> Vec<String> {
+        ["", "common.glsl", "main.glsl", "util.glsl"]
+            .map(String::from)
+            .to_vec()
+    }
+
+    // file, line, column, severity and message of a diagnostic
+    type Fields = (
+        Option<String>,
+        Option<usize>,
+        Option<usize>,
+        Severity,
+        String,
+    );
+
+    fn parsed(log: &str) -> Vec<Fields> {
+        parse_log(log, &names(), "main")
+            .into_iter()
+            .map(|x| (x.file, x.line, x.column, x.severity, x.message))
+            .collect()
+    }
+
+    #[test]
+    fn mesa_lines() {
+        assert_eq!(
+            mesa("0:12(21): error: `undefined_thing' undeclared"),
+            Some((
+                0,
+                12,
+                Some(21),
+                Severity::Error,
+                "`undefined_thing' undeclared"
+            ))
+        );
+        assert_eq!(
+            mesa("3:7(10): warning: `x' used uninitialized"),
+            Some((3, 7, Some(10), Severity::Warning, "`x' used uninitialized"))
+        );
+        assert_eq!(mesa("0(12) : error C1008: undefined variable \"x\""), None);
+        assert_eq!(mesa("ERROR: 0:12: 'x' : undeclared identifier"), None);
+    }
+
+    #[test]
+    fn nvidia_lines() {
+        assert_eq!(
+            nvidia("2(12) : error C1008: undefined variable \"x\""),
+            Some((
+                2,
+                12,
+                None,
+                Severity::Error,
+                "C1008: undefined variable \"x\""
+            ))
+        );
+        assert_eq!(
+            nvidia("1(3) : warning C7050: \"x\" might be used before being initialized"),
+            Some((
+                1,
+                3,
+                None,
+                Severity::Warning,
+                "C7050: \"x\" might be used before being initialized"
+            ))
+        );
+        assert_eq!(nvidia("0:12(21): error: `x' undeclared"), None);
+    }
+
+    #[test]
+    fn amd_lines() {
+        // amd, and intel on windows
+        assert_eq!(
+            amd("ERROR: 2:12: 'x' : undeclared identifier"),
+            Some((2, 12, None, Severity::Error, " 'x' : undeclared identifier"))
+        );
+        assert_eq!(
+            amd("WARNING: 3:4: 'y' : variable may be used before initialization"),
+            Some((
+                3,
+                4,
+                None,
+                Severity::Warning,
+                " 'y' : variable may be used before initialization"
+            ))
+        );
+        // apple
+        assert_eq!(
+            amd("ERROR: 1:5: Use of undeclared identifier 'x'"),
+            Some((
+                1,
+                5,
+                None,
+                Severity::Error,
+                " Use of undeclared identifier 'x'"
+            ))
+        );
+        assert_eq!(
+            amd("ERROR: 1 compilation errors.  No code generated."),
+            None
+        );
+    }
+
+    #[test]
+    fn log_with_files() {
+        let log = "0:5(12): error: `x' undeclared\n\
+                   3:7(10): warning: `y' used uninitialized\n";
+        assert_eq!(
+            parsed(log),
+            vec![
+                (None, None, None, Severity::Error, "`x' undeclared".into()),
+                (
+                    Some("util.glsl".into()),
+                    Some(7),
+                    Some(10),
+                    Severity::Warning,
+                    "`y' used uninitialized".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn log_without_code_generated() {
+        let log = "ERROR: 2:12: 'x' : undeclared identifier \n\
+                   ERROR: 2:12: '' : compilation terminated \n\
+                   ERROR: 2 compilation errors.  No code generated.\n\n";
+        assert_eq!(
+            parsed(log),
+            vec![(
+                Some("main.glsl".into()),
+                Some(12),
+                None,
+                Severity::Error,
+                "'x' : undeclared identifier".into()
+            ),]
+        );
+    }
+
+    #[test]
+    fn log_continuation_lines() {
+        let log =
+            "1(4) : error C1115: unable to find compatible overloaded function \"get(vec2)\"\n\
+                   candidates are: get(float)\n\
+                   \n\
+                   Link failed";
+        assert_eq!(
+            parsed(log),
+            vec![(
+                Some("common.glsl".into()),
+                Some(4),
+                None,
+                Severity::Error,
+                "C1115: unable to find compatible overloaded function \"get(vec2)\"\n\
+                 candidates are: get(float)\n\
+                 Link failed"
+                    .into()
+            )]
+        );
+
+        // before any diagnostic, it's one itself
+        assert_eq!(
+            parsed("Link failed"),
+            vec![(None, None, None, Severity::Error, "Link failed".into())]
+        );
+    }
+}
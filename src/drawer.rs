@@ -1,31 +1,83 @@
 use glium::Surface;
 
+use crate::args::MessageFormat;
 use crate::buffer::*;
+use crate::debug::{DebugView, Remap, View};
+use crate::debug_print::DebugPrinter;
+use crate::diagnostic::message;
+use crate::inspector::{frag_coord, texel_at, Inspector};
+use crate::nan::{NanCheck, NonFinite};
+use crate::overlay::Overlay;
 
 /// helper to actually draw the shaders
 pub struct Drawer {
-	// buffers that manage rendering
-    pub buffers: [Buffer; 5],
+    // buffers that manage rendering, the main image is the first
+    pub buffers: Vec<Buffer>,
 
-	// where the buffers render to
-	backbuffers: [glium::Texture2d; 5],
+    // order to draw the buffers in, so they draw after the buffers they read from
+    order: Vec<usize>,
 
-	// empty texture
+    // empty texture
     empty: glium::Texture2d,
 
-	// main program to copy to the framebuffer
+    // main program to copy to the framebuffer
     main_program: glium::Program,
 
-	// vertex buffer
+    // vertex buffer
     vertex_buffer: glium::VertexBuffer<Vert>,
-    
-	// size
-	pub width: u32,
+
+    // size
+    pub width: u32,
     pub height: u32,
+
+    // render scale, compared to the window
+    scale: f32,
+
+    // sum of all samples of the main image in linear color, when accumulating
+    accumulation: glium::Texture2d,
+
+    // average of the samples, in the sRGB the shader outputs
+    average: glium::Texture2d,
+
+    // how many samples to take, if accumulating
+    pub accumulate: Option<u32>,
+
+    // how many samples are in the accumulation texture
+    pub samples: u32,
+
+    // inputs used for the samples so far, these reset the accumulation when changed
+    accumulated_inputs: ((u32, u32), (u32, u32), (bool, bool)),
+
+    // whether to measure how long each buffer takes on the gpu
+    pub timing: bool,
+
+    // stats shown on top of the image
+    pub overlay: Overlay,
+
+    // value readout under the cursor
+    pub inspector: Inspector,
+
+    // view of all passes
+    pub debug: DebugView,
+
+    // finds NaN and Inf in the passes
+    pub nan_check: NanCheck,
+
+    // shows what the shaders printed
+    pub printer: DebugPrinter,
+
+    // how to print what's found, json keeps stdout for diagnostics
+    pub message_format: MessageFormat,
 }
 
 impl Drawer {
-    pub fn new(display: &glium::Display, width: u32, height: u32, scale: f32) -> Self {
+    pub fn new(
+        display: &glium::Display,
+        width: u32,
+        height: u32,
+        scale: f32,
+        accumulate: Option<u32>,
+    ) -> Self {
         // scaled
         let width = (width as f32 * scale) as u32;
         let height = (height as f32 * scale) as u32;
@@ -74,10 +126,28 @@ impl Drawer {
 				// where we get our image from
 				uniform sampler2D main_image;
 
+				// what to multiply the image with, to average accumulated samples
+				uniform float main_scale;
+
+				// whether to convert from the sRGB the shader outputs to linear color before, and back after
+				// samples are summed in linear color, so edges don't get darker
+				uniform bool to_linear;
+				uniform bool to_srgb;
+
 				void main() {
 
 					// just copy it
-					fragcol = texture(main_image, vpos);
+					vec4 color = texture(main_image, vpos);
+					if (to_linear) {
+						vec3 high = pow((max(color.rgb, 0.0) + 0.055) / 1.055, vec3(2.4));
+						color.rgb = mix(color.rgb / 12.92, high, step(0.04045, color.rgb));
+					}
+					color *= main_scale;
+					if (to_srgb) {
+						vec3 high = 1.055 * pow(max(color.rgb, 0.0), vec3(1.0 / 2.4)) - 0.055;
+						color.rgb = mix(color.rgb * 12.92, high, step(0.0031308, color.rgb));
+					}
+					fragcol = color;
 
 				}
 				",
@@ -91,16 +161,20 @@ impl Drawer {
         // empty texture
         let empty = glium::Texture2d::empty(display, 1, 1).unwrap();
 
-        // buffers
-		// TODO: PRETTYFY
-		let (buf0, backbuf0) = Buffer::new(display, width, height, None, [Channel::Buffer(0), Channel::None, Channel::None, Channel::None]); 
-		let (buf1, backbuf1) = Buffer::new(display, width, height, None, [Channel::None, Channel::None, Channel::None, Channel::None]); 
-		let (buf2, backbuf2) = Buffer::new(display, width, height, None, [Channel::None, Channel::None, Channel::None, Channel::None]); 
-		let (buf3, backbuf3) = Buffer::new(display, width, height, None, [Channel::None, Channel::None, Channel::None, Channel::None]); 
-		let (buf4, backbuf4) = Buffer::new(display, width, height, None, [Channel::None, Channel::None, Channel::None, Channel::None]); 
+        // where the samples are summed when accumulating
+        let accumulation = Self::accumulation_texture(display, width, height);
+        let average = Self::accumulation_texture(display, width, height);
 
-		let buffers = [buf0, buf1, buf2, buf3, buf4];
-		let backbuffers = [backbuf0, backbuf1, backbuf2, backbuf3, backbuf4];
+        // only an empty main image, until the shaders are loaded
+        let mut main = Buffer::new(
+            display,
+            "main",
+            Storage::default(),
+            None,
+            [Channel::None, Channel::None, Channel::None, Channel::None],
+            Vec::new(),
+        );
+        main.resize(display, display.get_framebuffer_dimensions(), scale);
 
         Self {
             empty,
@@ -108,46 +182,96 @@ impl Drawer {
             vertex_buffer,
             width,
             height,
-            buffers,
-			backbuffers,
+            scale,
+            buffers: vec![main],
+            order: vec![0],
+            accumulation,
+            average,
+            accumulate,
+            samples: 0,
+            accumulated_inputs: ((0, 0), (0, 0), (false, false)),
+            timing: false,
+            overlay: Overlay::new(display),
+            inspector: Inspector::new(display),
+            debug: DebugView::new(display),
+            nan_check: NanCheck::new(display),
+            printer: DebugPrinter::new(display),
+            message_format: MessageFormat::default(),
         }
     }
 
-    pub fn draw(
+    // makes the texture to sum samples in
+    fn accumulation_texture(display: &glium::Display, width: u32, height: u32) -> glium::Texture2d {
+        glium::Texture2d::empty_with_format(
+            display,
+            glium::texture::UncompressedFloatFormat::F32F32F32F32,
+            glium::texture::MipmapsOption::NoMipmap,
+            width,
+            height,
+        )
+        .expect("failed to make texture")
+    }
+
+    /// use the given buffers, drawn in the given order
+    /// the first buffer is the main image
+    pub fn set_passes(
         &mut self,
         display: &glium::Display,
-        time: f32,
-        frame: i32,
-        mouse_position: (u32, u32),
-        mouse_input: (bool, bool),
-        scale: f32,
+        buffers: Vec<Buffer>,
+        order: Vec<usize>,
     ) {
-        // get the image size
-        let resolution = display.get_framebuffer_dimensions();
+        self.buffers = buffers;
+        for buffer in self.buffers.iter_mut() {
+            buffer.resize(display, display.get_framebuffer_dimensions(), self.scale);
+        }
+        self.order = order;
+        self.debug.pass = self.debug.pass.min(self.buffers.len() - 1);
+        self.reset_accumulation();
+    }
 
-		// resize if needed
-        self.width = (resolution.0 as f32 * scale) as u32;
-        self.height = (resolution.1 as f32 * scale) as u32;
+    /// get the results of the last timed frame, waits for the gpu if needed
+    pub fn update_gpu_times(&mut self) {
+        for buffer in self.buffers.iter_mut() {
+            buffer.update_gpu_time();
+        }
+    }
 
-        // draw buffers
-        for (i, buffer) in self.buffers.iter_mut().enumerate() {
-            buffer.draw(
-                display,
-				i,
-                &mut self.backbuffers,
-                time,
-                frame,
-                mouse_position,
-                mouse_input,
-                scale,
-                &self.empty,
-            );
+    /// throw away all accumulated samples
+    pub fn reset_accumulation(&mut self) {
+        self.samples = 0;
+        self.accumulation
+            .as_surface()
+            .clear_color(0.0, 0.0, 0.0, 0.0);
+        self.average.as_surface().clear_color(0.0, 0.0, 0.0, 0.0);
+    }
+
+    /// whether all samples are taken, if accumulating
+    pub fn converged(&self) -> bool {
+        self.accumulate.is_some_and(|x| self.samples >= x)
+    }
+
+    /// the main image as it's shown on screen, averaged if accumulating
+    pub fn output(&self, display: &glium::Display) -> glium::Texture2d {
+        let output = Self::accumulation_texture(display, self.width, self.height);
+        self.blit(&mut output.as_surface());
+        output
+    }
+
+    // the main image, averaged if accumulating
+    fn main_image(&self) -> &glium::Texture2d {
+        match self.accumulate {
+            Some(_) => &self.average,
+            None => self.buffers[0].output(),
         }
+    }
 
-        // and draw to the main screen, grab the backbuffer as that's what's immediatly shown
-        let mut target = display.draw();
+    // draws the main image to the given surface
+    fn blit<S: Surface>(&self, target: &mut S) {
         let uniform = glium::uniform! {
-            main_image: self.backbuffers[0].sampled().wrap_function(glium::uniforms::SamplerWrapFunction::Clamp),
+            main_image: self.main_image().sampled().wrap_function(glium::uniforms::SamplerWrapFunction::Clamp),
+            main_scale: 1.0f32,
+            to_linear: false,
+            to_srgb: false,
         };
 
         target
@@ -159,6 +283,362 @@ impl Drawer {
                 &Default::default(),
             )
             .unwrap();
+    }
+
+    // adds the current main image to the accumulated samples, and averages them again
+    fn accumulate_sample(&mut self) {
+        let uniform = glium::uniform! {
+            main_image: self.buffers[0].output().sampled().wrap_function(glium::uniforms::SamplerWrapFunction::Clamp),
+            main_scale: 1.0f32,
+            to_linear: true,
+            to_srgb: false,
+        };
+
+        // add, instead of overwriting
+        let blend = glium::Blend {
+            color: glium::BlendingFunction::Addition {
+                source: glium::LinearBlendingFactor::One,
+                destination: glium::LinearBlendingFactor::One,
+            },
+            alpha: glium::BlendingFunction::Addition {
+                source: glium::LinearBlendingFactor::One,
+                destination: glium::LinearBlendingFactor::One,
+            },
+            constant_value: (0.0, 0.0, 0.0, 0.0),
+        };
+
+        self.accumulation
+            .as_surface()
+            .draw(
+                &self.vertex_buffer,
+                glium::index::NoIndices(glium::index::PrimitiveType::TriangleStrip),
+                &self.main_program,
+                &uniform,
+                &glium::DrawParameters {
+                    blend,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        self.samples += 1;
+
+        let uniform = glium::uniform! {
+            main_image: self.accumulation.sampled().wrap_function(glium::uniforms::SamplerWrapFunction::Clamp),
+            main_scale: 1.0 / self.samples as f32,
+            to_linear: false,
+            to_srgb: true,
+        };
+        self.average
+            .as_surface()
+            .draw(
+                &self.vertex_buffer,
+                glium::index::NoIndices(glium::index::PrimitiveType::TriangleStrip),
+                &self.main_program,
+                &uniform,
+                &Default::default(),
+            )
+            .unwrap();
+    }
+
+    /// render all buffers, and show the result
+    pub fn draw(&mut self, display: &glium::Display, info: &FrameInfo, scale: f32) {
+        // get the image size
+        let resolution = display.get_framebuffer_dimensions();
+
+        // resize if needed
+        let (width, height) = (
+            (resolution.0 as f32 * scale) as u32,
+            (resolution.1 as f32 * scale) as u32,
+        );
+        self.width = width;
+        self.height = height;
+        self.scale = scale;
+        for buffer in self.buffers.iter_mut() {
+            buffer.resize(display, resolution, scale);
+        }
+
+        // the main image can have it's own size
+        let main_size = self.buffers[0].output().dimensions();
+        if self.accumulation.dimensions() != main_size {
+            self.accumulation = Self::accumulation_texture(display, main_size.0, main_size.1);
+            self.average = Self::accumulation_texture(display, main_size.0, main_size.1);
+        }
+
+        // start over with accumulating if anything the image depends on changed
+        let inputs = ((width, height), info.mouse_position, info.mouse_input);
+        if inputs != self.accumulated_inputs {
+            self.accumulated_inputs = inputs;
+            self.reset_accumulation();
+        }
+
+        // no need to render anything new if all samples are taken
+        if !self.converged() {
+            // jitter the main image if we're accumulating, in window pixels
+            let main_info = match self.accumulate {
+                Some(_) => FrameInfo {
+                    sample: self.samples,
+                    jitter: [
+                        (halton(self.samples + 1, 2) - 0.5) / scale,
+                        (halton(self.samples + 1, 3) - 0.5) / scale,
+                    ],
+                    ..*info
+                },
+                None => *info,
+            };
+
+            // results of the previous frame should be done by now
+            self.update_gpu_times();
+
+            // draw buffers, after the buffers they read from
+            let print = self.printer.request.take();
+            for index in 0..self.order.len() {
+                let i = self.order[index];
+                let pass_info = if i == 0 { &main_info } else { info };
+
+                // print with the same inputs as the actual draw
+                if let Some(cursor) = print {
+                    self.debug_print(display, i, pass_info, cursor);
+                }
+
+                // only time buffers that actually draw something
+                // the overlay shows the times, so also measure if it's visible
+                if (self.timing || self.overlay.visible) && self.buffers[i].program.is_some() {
+                    self.buffers[i].start_timing(display);
+                }
+
+                self.buffers[i].draw(display, &self.buffers, pass_info, &self.empty);
+                self.buffers[i].swap();
+            }
+
+            // look for NaN and Inf, until the first one is found
+            if self.nan_check.enabled && self.nan_check.found.is_none() {
+                self.check_finite(display, info.frame);
+            }
+
+            // and add the new sample
+            if self.accumulate.is_some() {
+                self.accumulate_sample();
+            }
+        }
+
+        self.present(display, info, scale);
+    }
+
+    /// run only the main pass again for the frame that was just drawn, with other inputs
+    /// it reads what the other passes drew this frame, and its own previous frame, as it did before
+    pub fn draw_main(&mut self, display: &glium::Display, info: &FrameInfo) {
+        // swap back, so the previous frame is the output again, and draw over what it drew
+        self.buffers[0].swap();
+        self.buffers[0].draw(display, &self.buffers, info, &self.empty);
+        self.buffers[0].swap();
+    }
+
+    // run a pass at the pixel under the given position in window pixels, and show what it printed
+    fn debug_print(
+        &self,
+        display: &glium::Display,
+        index: usize,
+        info: &FrameInfo,
+        cursor: (f64, f64),
+    ) {
+        if self.buffers[index].program.is_none() {
+            return;
+        }
+
+        let window = display.get_framebuffer_dimensions();
+        let buffer = &self.buffers[index];
+        let size = buffer.output().dimensions();
+        let texel = texel_at(cursor, size, [0.0, 0.0, window.0 as f32, window.1 as f32]);
+        buffer.debug_print(
+            &self.buffers,
+            info,
+            &self.empty,
+            frag_coord(texel, size, buffer.resolution),
+            self.printer.target(),
+        );
+        self.printer
+            .report(&self.buffers[index], self.message_format);
+    }
+
+    /// show the last rendered image again, without rendering the buffers
+    /// used when only the overlay or inspector changed, or to print when paused
+    pub fn present(&mut self, display: &glium::Display, info: &FrameInfo, scale: f32) {
+        // nothing new was drawn, such as when paused or converged, so print with the last inputs
+        if let Some(cursor) = self.printer.request.take() {
+            for &i in &self.order {
+                self.debug_print(display, i, info, cursor);
+            }
+        }
+
+        // and draw to the main screen, grab the backbuffer as that's what's immediatly shown
+        let mut target = display.draw();
+        let window = target.get_dimensions();
+        let rects = self.debug.rects(self.buffers.len(), window);
+        let solo = self.debug.pass;
+        match self.debug.view {
+            View::Image if self.debug.remap == Remap::Off => self.blit(&mut target),
+            View::Image => {
+                self.debug.draw(
+                    &mut target,
+                    &self.vertex_buffer,
+                    self.main_image(),
+                    rects[0],
+                );
+            }
+            View::Solo => {
+                self.debug.draw(
+                    &mut target,
+                    &self.vertex_buffer,
+                    self.buffers[solo].output(),
+                    rects[solo],
+                );
+
+                // at the top right, out of the way of the stats
+                self.overlay.draw_text(
+                    display,
+                    &mut target,
+                    [window.0 as f32, 0.0],
+                    std::slice::from_ref(&self.buffers[solo].name),
+                );
+            }
+            View::Tiles => {
+                target.clear_color(0.0, 0.0, 0.0, 1.0);
+                for (buffer, rect) in self.buffers.iter().zip(&rects) {
+                    self.debug
+                        .draw(&mut target, &self.vertex_buffer, buffer.output(), *rect);
+                    self.overlay.draw_text(
+                        display,
+                        &mut target,
+                        [rect[0], rect[1]],
+                        std::slice::from_ref(&buffer.name),
+                    );
+                }
+            }
+        }
+
+        // show NaN and Inf in a loud colour
+        if self.nan_check.enabled {
+            let shown = match self.debug.view {
+                View::Image => vec![(self.main_image(), rects[0])],
+                View::Solo => vec![(self.buffers[solo].output(), rects[solo])],
+                View::Tiles => self
+                    .buffers
+                    .iter()
+                    .map(Buffer::output)
+                    .zip(rects.iter().copied())
+                    .collect(),
+            };
+            for (texture, rect) in shown {
+                self.nan_check
+                    .highlight(&mut target, &self.vertex_buffer, texture, rect);
+            }
+        }
+
+        // and show the stats on top
+        if self.overlay.visible {
+            let stats = self.stats(info, scale);
+            self.overlay.draw(display, &mut target, &stats);
+        }
+
+        // and the value under the cursor, of the tile it's on or the chosen pass
+        let inspected = match self.debug.view {
+            View::Tiles => (0..self.buffers.len()).find(|&i| self.inspector.hovers(rects[i])),
+            _ => Some(solo),
+        };
+        if let Some(pass) = inspected.filter(|_| self.inspector.active) {
+            self.inspector.draw(
+                display,
+                &mut target,
+                &self.overlay,
+                &self.buffers[pass],
+                rects[pass],
+            );
+        }
+
         target.finish().unwrap();
     }
+
+    /// checks all passes that were drawn for NaN and Inf, in the order they're drawn, and reports the first one found
+    pub fn check_finite(&mut self, display: &glium::Display, frame: i32) {
+        for &i in &self.order {
+            let buffer = &self.buffers[i];
+            if buffer.program.is_none() {
+                continue;
+            }
+
+            let (nan, inf) = self
+                .nan_check
+                .check(display, &self.vertex_buffer, buffer.output());
+            if nan || inf {
+                let found = NonFinite {
+                    frame,
+                    pass: buffer.name.clone(),
+                    nan,
+                    inf,
+                };
+                message(self.message_format, &format!("Found {}", found));
+                self.nan_check.found = Some(found);
+                return;
+            }
+        }
+    }
+
+    // lines of text to show in the overlay
+    fn stats(&self, info: &FrameInfo, scale: f32) -> Vec<String> {
+        let mut lines = vec![
+            format!(
+                "{:.1} fps, {:.2}ms",
+                info.frame_rate,
+                1000.0 / info.frame_rate
+            ),
+            format!("iTime: {:.3}", info.time),
+            format!("iFrame: {}", info.frame),
+            format!(
+                "resolution: {}x{}, scale: {}",
+                self.width, self.height, scale
+            ),
+            format!(
+                "iMouse: {} {} {} {}",
+                info.mouse_position.0,
+                info.mouse_position.1,
+                info.mouse_input.0 as u32,
+                info.mouse_input.1 as u32
+            ),
+        ];
+
+        // accumulation progress
+        if let Some(x) = self.accumulate {
+            lines.push(format!("samples: {}/{}", self.samples, x));
+        }
+
+        // first NaN or Inf, if checking
+        if self.nan_check.enabled {
+            lines.push(match &self.nan_check.found {
+                Some(found) => found.to_string(),
+                None => "no NaN or Inf".to_string(),
+            });
+        }
+
+        // and all passes that were measured
+        for buffer in &self.buffers {
+            if let Some(time) = buffer.gpu_time {
+                lines.push(format!("{}: {:.3}ms", buffer.name, time));
+            }
+        }
+
+        lines
+    }
+}
+
+// halton sequence, gives well distributed points in [0, 1)
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.0;
+    let mut result = 0.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
 }
@@ -1,34 +1,62 @@
-#version 140
-
-// internal inputs, from the fragment shader 
-in vec2 _internal_vpos;
-out vec4 _internal_fragcol;
-
-// shadertoy inputs
-uniform vec3 iResolution;
-uniform vec4 iMouse;
-uniform float iTime;
-uniform int iFrame;
-
-// textures
-uniform sampler2D iChannel0;
-uniform sampler2D iChannel1;
-uniform sampler2D iChannel2;
-uniform sampler2D iChannel3;
-
+{version}
+
+// defines from the command line and config
+{defines}
+
+// outputs, one per attachment
+{outputs}
+
+{inputs}
+
+// every pair of pixels of the output keeps one print, the value and where it came from
+int _internal_debug_count = 0;
+vec4 _internal_debug_value = vec4(0.0);
+vec4 _internal_debug_info = vec4(0.0);
+
+// info is the line, source (1 is common, 2 is the shader), components (0 for int), and the total amount of prints
+void _internal_debug_print(vec4 value, int line, int source, int components) {{
+	if (_internal_debug && _internal_debug_count == int(gl_FragCoord.x) / 2) {{
+		_internal_debug_value = value;
+		_internal_debug_info = vec4(line, source, components, 0.0);
+	}}
+	_internal_debug_count++;
+}}
+void _internal_debug_print(float value, int line, int source) {{ _internal_debug_print(vec4(value), line, source, 1); }}
+void _internal_debug_print(vec2 value, int line, int source) {{ _internal_debug_print(value.xyyy, line, source, 2); }}
+void _internal_debug_print(vec3 value, int line, int source) {{ _internal_debug_print(value.xyzz, line, source, 3); }}
+void _internal_debug_print(vec4 value, int line, int source) {{ _internal_debug_print(value, line, source, 4); }}
+void _internal_debug_print(int value, int line, int source) {{ _internal_debug_print(vec4(value), line, source, 0); }}
+#define debugPrint(value) _internal_debug_print(value, __LINE__, __FILE__)
 
 // shadertoy common inserted here, 
 // with rust format strings
-{}
+// line numbers are reset, so they match the file
+#line 1 1
+{common}
 
 // shadertoy source is inserted here
-{}
+#line 1 2
+{shader}
 
 // double braces to escape rust's formatter
 void main() {{
 
 	// shadertoy has mainImage, which takes in the fragcolor to output, and the UV (frag coordinate) multiplied by the resolution
 	// we have these in the file, so we can just grab the function and render it
-	mainImage(_internal_fragcol, _internal_vpos * iResolution.xy);
+	// the jitter is zero unless the main image is being accumulated
+	// with multiple attachments, mainImage gets an output for each of them, before the frag coordinate
+	if (!_internal_debug) {{
+		mainImage({arguments}, _internal_vpos * iResolution.xy + _internal_jitter);
+		return;
+	}}
+
+	// when debug printing, run the debug pixel instead, and output the prints
+	mainImage({arguments}, _internal_debug_coord);
+	vec4 _internal_print = int(gl_FragCoord.x) % 2 == 0
+		? _internal_debug_value
+		: vec4(_internal_debug_info.xyz, _internal_debug_count);
+
+	// to every output, as it's not known which one ends up in the print target
+	{assign} = _internal_print;
 
 }}
\ No newline at end of file
@@ -0,0 +1,77 @@
+/// order to run passes in, so every pass runs after the passes it reads from
+/// inputs has the passes each pass reads from, and passes are visited starting from first
+/// reads that would need a pass to run before itself are returned as well, as (reader, pass) pairs
+/// these read the previous frame instead
+pub fn execution_order(inputs: &[Vec<usize>], first: usize) -> (Vec<usize>, Vec<(usize, usize)>) {
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    enum State {
+        Unvisited,
+        Visiting,
+        Done,
+    }
+
+    // depth first, a pass goes in the order once all its inputs are in it
+    fn visit(
+        pass: usize,
+        inputs: &[Vec<usize>],
+        state: &mut [State],
+        order: &mut Vec<usize>,
+        previous: &mut Vec<(usize, usize)>,
+    ) {
+        state[pass] = State::Visiting;
+        for &input in &inputs[pass] {
+            match state[input] {
+                State::Unvisited => visit(input, inputs, state, order, previous),
+                // still waiting on this pass, so it can only be read from the previous frame
+                State::Visiting => previous.push((pass, input)),
+                State::Done => (),
+            }
+        }
+        state[pass] = State::Done;
+        order.push(pass);
+    }
+
+    let mut state = vec![State::Unvisited; inputs.len()];
+    let mut order = Vec::with_capacity(inputs.len());
+    let mut previous = Vec::new();
+
+    // passes nothing depends on still run, after the first one
+    for pass in std::iter::once(first).chain(0..inputs.len()) {
+        if state[pass] == State::Unvisited {
+            visit(pass, inputs, &mut state, &mut order, &mut previous);
+        }
+    }
+
+    (order, previous)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inputs_run_first() {
+        let inputs = [vec![1, 2], vec![2], vec![]];
+        assert_eq!(execution_order(&inputs, 0), (vec![2, 1, 0], vec![]));
+    }
+
+    #[test]
+    fn self_read() {
+        let inputs = [vec![0]];
+        assert_eq!(execution_order(&inputs, 0), (vec![0], vec![(0, 0)]));
+    }
+
+    #[test]
+    fn two_pass_cycle() {
+        // main reads a, which reads main, so a gets the previous frame of main
+        let inputs = [vec![1], vec![0]];
+        assert_eq!(execution_order(&inputs, 0), (vec![1, 0], vec![(1, 0)]));
+    }
+
+    #[test]
+    fn unreachable_pass() {
+        // nothing reads 2, it still runs, after main
+        let inputs = [vec![1], vec![], vec![1]];
+        assert_eq!(execution_order(&inputs, 0), (vec![1, 0, 2], vec![]));
+    }
+}
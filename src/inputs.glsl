@@ -0,0 +1,25 @@
+// internal inputs, from the vertex shader
+in vec2 _internal_vpos;
+
+// shadertoy inputs
+uniform vec3 iResolution;
+uniform vec3 iChannelResolution[4];
+uniform vec4 iMouse;
+uniform float iTime;
+uniform float iTimeDelta;
+uniform float iFrameRate;
+uniform int iFrame;
+uniform int iSampleIndex;
+
+// subpixel offset when accumulating
+uniform vec2 _internal_jitter;
+
+// textures
+uniform sampler2D iChannel0;
+uniform sampler2D iChannel1;
+uniform sampler2D iChannel2;
+uniform sampler2D iChannel3;
+
+// debug printing, when set only the given fragCoord is run
+uniform bool _internal_debug;
+uniform vec2 _internal_debug_coord;
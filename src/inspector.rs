@@ -0,0 +1,222 @@
+use glium::Surface;
+
+use crate::buffer::{Buffer, Vert};
+use crate::overlay::Overlay;
+
+// how many texels the magnified grid shows in each direction, odd so there is a center
+const ZOOM_TEXELS: i32 = 15;
+
+// how big a texel is in the magnified grid, in screen pixels
+const ZOOM_PIXELS: f32 = 12.0;
+
+// distance between the cursor and the magnified grid, in screen pixels
+const CURSOR_OFFSET: f32 = 20.0;
+
+/// shows the exact value of a pass under the cursor, and a magnified grid around it
+pub struct Inspector {
+    // whether to show it
+    pub active: bool,
+
+    // cursor position in window pixels, from the top left
+    pub cursor: (f64, f64),
+
+    // program to draw the magnified grid
+    program: glium::Program,
+
+    // quad to draw the grid on
+    vertex_buffer: glium::VertexBuffer<Vert>,
+}
+
+impl Inspector {
+    pub fn new(display: &glium::Display) -> Self {
+        // outputs_srgb, same as the main image, so the grid looks like the screen
+        let program = glium::program::Program::new(
+            display,
+            glium::program::ProgramCreationInput::SourceCode {
+                vertex_shader: "
+            #version 140
+
+            in vec2 pos;
+            out vec2 local;
+
+            // size of the screen, to go from pixels to gl coordinates
+            uniform vec2 resolution;
+
+            // top left and size of the grid, in pixels
+            uniform vec4 rect;
+
+            void main() {
+                local = pos * rect.zw;
+                vec2 pixel = rect.xy + local;
+
+                // pixels go from the top left, gl goes from the bottom left
+                gl_Position = vec4(pixel / resolution * vec2(2.0, -2.0) + vec2(-1.0, 1.0), 0.0, 1.0);
+            }
+            ",
+                tessellation_control_shader: None,
+                tessellation_evaluation_shader: None,
+                geometry_shader: None,
+                fragment_shader: "
+            #version 140
+
+            in vec2 local;
+            out vec4 fragcol;
+
+            uniform sampler2D image;
+
+            // texel under the cursor
+            uniform ivec2 center;
+
+            // texels in each direction, and how big they are on screen
+            uniform int texels;
+            uniform float zoom;
+
+            void main() {
+                ivec2 cell = ivec2(floor(local / zoom));
+                vec2 edge = local - vec2(cell) * zoom;
+
+                // textures have the first row at the bottom, the grid at the top
+                ivec2 texel = center + ivec2(cell.x - texels / 2, texels / 2 - cell.y);
+                bool inside = all(greaterThanEqual(texel, ivec2(0))) && all(lessThan(texel, textureSize(image, 0)));
+                vec4 color = inside ? texelFetch(image, texel, 0) : vec4(0.0);
+
+                if (cell == ivec2(texels / 2) && (min(edge.x, edge.y) < 2.0 || max(edge.x, edge.y) > zoom - 2.0)) {
+                    // outline the texel under the cursor
+                    fragcol = vec4(1.0, 0.2, 0.2, 1.0);
+                } else if (min(edge.x, edge.y) < 1.0) {
+                    // grid lines
+                    fragcol = vec4(0.2, 0.2, 0.2, 1.0);
+                } else {
+                    fragcol = vec4(color.rgb, 1.0);
+                }
+            }
+            ",
+                transform_feedback_varyings: None,
+                outputs_srgb: true,
+                uses_point_size: false,
+            },
+        )
+        .expect("failed to make inspector program");
+
+        let vertex_buffer = glium::VertexBuffer::new(
+            display,
+            &[
+                Vert { pos: [0.0, 0.0] },
+                Vert { pos: [1.0, 0.0] },
+                Vert { pos: [0.0, 1.0] },
+                Vert { pos: [1.0, 1.0] },
+            ],
+        )
+        .unwrap();
+
+        Self {
+            active: false,
+            cursor: (0.0, 0.0),
+            program,
+            vertex_buffer,
+        }
+    }
+
+    /// whether the cursor is in the given rect, as left, top, width and height in window pixels
+    pub fn hovers(&self, rect: [f32; 4]) -> bool {
+        let (x, y) = (self.cursor.0 as f32, self.cursor.1 as f32);
+        x >= rect[0] && y >= rect[1] && x < rect[0] + rect[2] && y < rect[1] + rect[3]
+    }
+
+    /// texel of a texture under the cursor, with the texture stretched over the given rect
+    pub fn texel(&self, texture: &glium::Texture2d, rect: [f32; 4]) -> (u32, u32) {
+        texel_at(self.cursor, texture.dimensions(), rect)
+    }
+
+    /// draw the magnified grid and the value under the cursor
+    pub fn draw<S: Surface>(
+        &self,
+        display: &glium::Display,
+        target: &mut S,
+        overlay: &Overlay,
+        buffer: &Buffer,
+        rect: [f32; 4],
+    ) {
+        let texture = buffer.output();
+        let (width, height) = target.get_dimensions();
+        let texel = self.texel(texture, rect);
+
+        // read back the exact value
+        let value: Vec<Vec<(f32, f32, f32, f32)>> = texture
+            .main_level()
+            .first_layer()
+            .into_image(None)
+            .expect("failed to get texture image")
+            .raw_read(&glium::Rect {
+                left: texel.0,
+                bottom: texel.1,
+                width: 1,
+                height: 1,
+            });
+        let (r, g, b, a) = value[0][0];
+
+        // next to the cursor, but flipped to the other side if it would go off screen
+        let size = ZOOM_TEXELS as f32 * ZOOM_PIXELS;
+        let place = |cursor: f32, screen: u32| {
+            if cursor + CURSOR_OFFSET + size > screen as f32 {
+                (cursor - CURSOR_OFFSET - size).max(0.0)
+            } else {
+                cursor + CURSOR_OFFSET
+            }
+        };
+        let pos = [
+            place(self.cursor.0 as f32, width),
+            place(self.cursor.1 as f32, height),
+        ];
+
+        let uniforms = glium::uniform! {
+            resolution: [width as f32, height as f32],
+            rect: [pos[0], pos[1], size, size],
+            image: texture,
+            center: [texel.0 as i32, texel.1 as i32],
+            texels: ZOOM_TEXELS,
+            zoom: ZOOM_PIXELS,
+        };
+
+        target
+            .draw(
+                &self.vertex_buffer,
+                glium::index::NoIndices(glium::index::PrimitiveType::TriangleStrip),
+                &self.program,
+                &uniforms,
+                &Default::default(),
+            )
+            .unwrap();
+
+        // and the values below it
+        let frag_coord = frag_coord(texel, texture.dimensions(), buffer.resolution);
+        let lines = [
+            format!("pass: {}", buffer.name),
+            format!("fragCoord: {} {}", frag_coord[0], frag_coord[1]),
+            format!("r: {}", r),
+            format!("g: {}", g),
+            format!("b: {}", b),
+            format!("a: {}", a),
+        ];
+        overlay.draw_text(display, target, [pos[0], pos[1] + size], &lines);
+    }
+}
+
+/// texel of a texture of the given size at a position in window pixels, with the texture stretched over the given rect
+pub fn texel_at(pos: (f64, f64), size: (u32, u32), rect: [f32; 4]) -> (u32, u32) {
+    let (width, height) = size;
+    let x = (pos.0 - rect[0] as f64) / rect[2].max(1.0) as f64 * width as f64;
+    let y = (1.0 - (pos.1 - rect[1] as f64) / rect[3].max(1.0) as f64) * height as f64;
+    (
+        (x.max(0.0) as u32).min(width.saturating_sub(1)),
+        (y.max(0.0) as u32).min(height.saturating_sub(1)),
+    )
+}
+
+/// fragCoord of the center of a texel of a texture of the given size, fragCoord goes up to the resolution of the pass
+pub fn frag_coord(texel: (u32, u32), size: (u32, u32), resolution: [f32; 2]) -> [f32; 2] {
+    [
+        (texel.0 as f32 + 0.5) / size.0 as f32 * resolution[0],
+        (texel.1 as f32 + 0.5) / size.1 as f32 * resolution[1],
+    ]
+}
@@ -0,0 +1,249 @@
+/// files of the built in library, by the name they're included with
+const FILES: &[(&str, &str)] = &[
+    (
+        "shadercrab/version.glsl",
+        include_str!("shadercrab/version.glsl"),
+    ),
+    ("shadercrab/hash.glsl", include_str!("shadercrab/hash.glsl")),
+    (
+        "shadercrab/noise.glsl",
+        include_str!("shadercrab/noise.glsl"),
+    ),
+    ("shadercrab/math.glsl", include_str!("shadercrab/math.glsl")),
+    ("shadercrab/sdf.glsl", include_str!("shadercrab/sdf.glsl")),
+    (
+        "shadercrab/camera.glsl",
+        include_str!("shadercrab/camera.glsl"),
+    ),
+    (
+        "shadercrab/color.glsl",
+        include_str!("shadercrab/color.glsl"),
+    ),
+    (
+        "shadercrab/tonemap.glsl",
+        include_str!("shadercrab/tonemap.glsl"),
+    ),
+];
+
+/// source of a library file, if there is one with this name
+pub fn get(name: &str) -> Option<&'static str> {
+    FILES.iter().find(|x| x.0 == name).map(|x| x.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer::Vert;
+    use crate::preprocess::Preprocessor;
+    use crate::program::{load_program, ProgramSource, Version};
+    use glium::backend::Facade;
+    use glium::glutin;
+    use glium::Surface;
+    use image::RgbaImage;
+    use std::path::{Path, PathBuf};
+
+    // size of the images
+    const SIZE: u32 = 64;
+
+    // how far a channel can be off, out of 255, as drivers round differently
+    const TOLERANCE: u8 = 3;
+
+    // how many pixels can be further off, as hashes can turn out completely different when rounded differently
+    const MAX_WRONG: usize = (SIZE * SIZE / 100) as usize;
+
+    // name of the image, file to include, and the color of the pixel
+    // uv goes from 0 to 1, p from -1 to 1, and cell is the integer pixel coordinate
+    const CASES: &[(&str, &str, &str)] = &[
+        ("hash11", "hash", "vec3(hash11(cell.x + 64.0 * cell.y))"),
+        ("hash12", "hash", "vec3(hash12(cell))"),
+        ("hash13", "hash", "vec3(hash13(vec3(cell, 7.0)))"),
+        ("hash22", "hash", "vec3(hash22(cell), 0.0)"),
+        ("hash33", "hash", "hash33(vec3(cell, 7.0))"),
+        ("valueNoise2", "noise", "vec3(valueNoise(p * 4.0) * 0.5 + 0.5)"),
+        ("valueNoise3", "noise", "vec3(valueNoise(vec3(p * 4.0, 0.5)) * 0.5 + 0.5)"),
+        ("perlinNoise2", "noise", "vec3(perlinNoise(p * 4.0) * 0.5 + 0.5)"),
+        ("perlinNoise3", "noise", "vec3(perlinNoise(vec3(p * 4.0, 0.5)) * 0.5 + 0.5)"),
+        ("simplexNoise2", "noise", "vec3(simplexNoise(p * 4.0) * 0.5 + 0.5)"),
+        ("simplexNoise3", "noise", "vec3(simplexNoise(vec3(p * 4.0, 0.5)) * 0.5 + 0.5)"),
+        ("worleyNoise2", "noise", "vec3(worleyNoise(p * 4.0))"),
+        ("worleyNoise3", "noise", "vec3(worleyNoise(vec3(p * 4.0, 0.5)))"),
+        ("rotate2d", "math", "vec3(rotate2d(1.0) * p * 0.5 + 0.5, 0.0)"),
+        ("rotateX", "math", "rotateX(1.0) * vec3(p, 0.5) * 0.5 + 0.5"),
+        ("rotateY", "math", "rotateY(1.0) * vec3(p, 0.5) * 0.5 + 0.5"),
+        ("rotateZ", "math", "rotateZ(1.0) * vec3(p, 0.5) * 0.5 + 0.5"),
+        ("rotateAxis", "math", "rotateAxis(normalize(vec3(1.0, 2.0, 3.0)), 1.0) * vec3(p, 0.5) * 0.5 + 0.5"),
+        ("sdSphere", "sdf", "vec3(sdSphere(vec3(p, 0.0), 0.5) * 0.5 + 0.5)"),
+        ("sdBox3", "sdf", "vec3(sdBox(vec3(p, 0.2), vec3(0.5, 0.3, 0.4)) * 0.5 + 0.5)"),
+        ("sdRoundBox", "sdf", "vec3(sdRoundBox(vec3(p, 0.0), vec3(0.4, 0.3, 0.3), 0.1) * 0.5 + 0.5)"),
+        ("sdTorus", "sdf", "vec3(sdTorus(vec3(p.x, 0.0, p.y), vec2(0.5, 0.2)) * 0.5 + 0.5)"),
+        ("sdCylinder", "sdf", "vec3(sdCylinder(vec3(p, 0.0), 0.5, 0.3) * 0.5 + 0.5)"),
+        ("sdCapsule", "sdf", "vec3(sdCapsule(vec3(p, 0.0), vec3(-0.5, -0.3, 0.0), vec3(0.5, 0.3, 0.0), 0.2) * 0.5 + 0.5)"),
+        ("sdPlane", "sdf", "vec3(sdPlane(vec3(p, 0.0), normalize(vec3(1.0, 1.0, 0.0)), 0.2) * 0.5 + 0.5)"),
+        ("sdCircle", "sdf", "vec3(sdCircle(p, 0.5) * 0.5 + 0.5)"),
+        ("sdBox2", "sdf", "vec3(sdBox(p, vec2(0.5, 0.3)) * 0.5 + 0.5)"),
+        ("sdSegment", "sdf", "vec3(sdSegment(p, vec2(-0.5, -0.3), vec2(0.5, 0.3)) * 0.5 + 0.5)"),
+        ("opUnion", "sdf", "vec3(opUnion(sdCircle(p - vec2(0.2, 0.0), 0.4), sdBox(p + vec2(0.2, 0.0), vec2(0.3))) * 0.5 + 0.5)"),
+        ("opSubtraction", "sdf", "vec3(opSubtraction(sdCircle(p - vec2(0.2, 0.0), 0.4), sdBox(p + vec2(0.2, 0.0), vec2(0.3))) * 0.5 + 0.5)"),
+        ("opIntersection", "sdf", "vec3(opIntersection(sdCircle(p - vec2(0.2, 0.0), 0.4), sdBox(p + vec2(0.2, 0.0), vec2(0.3))) * 0.5 + 0.5)"),
+        ("opSmoothUnion", "sdf", "vec3(opSmoothUnion(sdCircle(p - vec2(0.2, 0.0), 0.4), sdBox(p + vec2(0.2, 0.0), vec2(0.3)), 0.2) * 0.5 + 0.5)"),
+        ("opSmoothSubtraction", "sdf", "vec3(opSmoothSubtraction(sdCircle(p - vec2(0.2, 0.0), 0.4), sdBox(p + vec2(0.2, 0.0), vec2(0.3)), 0.2) * 0.5 + 0.5)"),
+        ("opSmoothIntersection", "sdf", "vec3(opSmoothIntersection(sdCircle(p - vec2(0.2, 0.0), 0.4), sdBox(p + vec2(0.2, 0.0), vec2(0.3)), 0.2) * 0.5 + 0.5)"),
+        ("opRound", "sdf", "vec3(opRound(sdBox(p, vec2(0.3)), 0.1) * 0.5 + 0.5)"),
+        ("opOnion", "sdf", "vec3(opOnion(sdCircle(p, 0.5), 0.05) * 0.5 + 0.5)"),
+        ("opRepeat2", "sdf", "vec3(sdCircle(opRepeat(p, vec2(0.5)), 0.15) * 0.5 + 0.5)"),
+        ("opRepeat3", "sdf", "vec3(sdSphere(opRepeat(vec3(p, 0.0), vec3(0.5)), 0.15) * 0.5 + 0.5)"),
+        ("cameraMatrix", "camera", "cameraMatrix(vec3(0.0, 1.0, -3.0), vec3(0.0), 0.3) * vec3(p, 1.0) * 0.5 + 0.5"),
+        ("cameraRay", "camera", "cameraRay(fragCoord, iResolution.xy, vec3(0.0, 1.0, -3.0), vec3(0.0), 1.5) * 0.5 + 0.5"),
+        ("orbitCamera", "camera", "orbitCamera(p.x * 3.14159, p.y * 1.5, 1.0) * 0.5 + 0.5"),
+        ("srgbToLinear", "color", "srgbToLinear(vec3(uv, 0.5))"),
+        ("linearToSrgb", "color", "linearToSrgb(vec3(uv, 0.5))"),
+        ("rgbToHsv", "color", "rgbToHsv(vec3(uv, 0.5))"),
+        ("hsvToRgb", "color", "hsvToRgb(vec3(uv, 1.0))"),
+        ("rgbToOklab", "color", "rgbToOklab(vec3(uv, 0.5)) * vec3(1.0, 2.0, 2.0) + vec3(0.0, 0.5, 0.5)"),
+        ("oklabToRgb", "color", "oklabToRgb(vec3(0.7, p * 0.2))"),
+        ("luminance", "color", "vec3(luminance(vec3(uv, 0.5)))"),
+        ("tonemapReinhard", "tonemap", "tonemapReinhard(vec3(uv.x * 8.0) * vec3(1.0, uv.y, 0.5))"),
+        ("tonemapReinhardWhite", "tonemap", "tonemapReinhard(vec3(uv.x * 8.0) * vec3(1.0, uv.y, 0.5), 4.0)"),
+        ("tonemapAces", "tonemap", "tonemapAces(vec3(uv.x * 8.0) * vec3(1.0, uv.y, 0.5))"),
+        ("tonemapUncharted2", "tonemap", "tonemapUncharted2(vec3(uv.x * 8.0) * vec3(1.0, uv.y, 0.5))"),
+        ("tonemapHejl", "tonemap", "tonemapHejl(vec3(uv.x * 8.0) * vec3(1.0, uv.y, 0.5))"),
+    ];
+
+    // every function of the library, rendered and compared to the image in tests/golden
+    // set SHADERCRAB_UPDATE_GOLDEN to write the images instead, after changing a function on purpose
+    // this needs a display, so it only runs with --ignored, such as under xvfb-run
+    #[test]
+    #[ignore = "needs an OpenGL context"]
+    fn golden_images() {
+        let (_event_loop, facade) =
+            context().expect("failed to make an OpenGL context, this needs a display");
+
+        let update = std::env::var_os("SHADERCRAB_UPDATE_GOLDEN").is_some();
+        let failed = CASES
+            .iter()
+            .filter(|(name, file, color)| {
+                let image = render(&facade, name, file, color);
+                let path = golden(name);
+                if update {
+                    image.save(&path).expect("failed to save golden image");
+                    return false;
+                }
+
+                let expected = image::open(&path)
+                    .unwrap_or_else(|x| panic!("failed to open {:?}: {}", path, x))
+                    .into_rgba8();
+                let wrong = image
+                    .pixels()
+                    .zip(expected.pixels())
+                    .filter(|(a, b)| a.0.iter().zip(b.0).any(|(a, b)| a.abs_diff(b) > TOLERANCE))
+                    .count();
+                if wrong > MAX_WRONG {
+                    println!("{} has {} pixels that differ from {:?}", name, wrong, path);
+                }
+                wrong > MAX_WRONG
+            })
+            .count();
+        assert_eq!(
+            failed, 0,
+            "some library functions don't match their golden image"
+        );
+    }
+
+    // where the golden image of a function is
+    fn golden(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("golden")
+            .join(format!("{}.png", name))
+    }
+
+    // render a pass that includes the file, with the given color
+    fn render<F: Facade>(facade: &F, name: &str, file: &str, color: &str) -> RgbaImage {
+        let shader = format!(
+            "#include <shadercrab/{}.glsl>\n\
+             void mainImage(out vec4 fragColor, in vec2 fragCoord) {{\n\
+             \tvec2 uv = fragCoord / iResolution.xy;\n\
+             \tvec2 p = uv * 2.0 - 1.0;\n\
+             \tvec2 cell = floor(fragCoord);\n\
+             \tfragColor = vec4({}, 1.0);\n\
+             }}\n",
+            file, color
+        );
+        let path = Path::new(name);
+        let shader = Preprocessor::new(Vec::new())
+            .expand(&shader, path, 2)
+            .expect("failed to include library file");
+        let source = ProgramSource::new(name, Vec::new(), "", &shader, 1, &[], Version::default());
+        let program = load_program(facade, &source)
+            .unwrap_or_else(|x| panic!("failed to compile {}: {}", name, x));
+
+        let texture = glium::Texture2d::empty_with_format(
+            facade,
+            glium::texture::UncompressedFloatFormat::F32F32F32F32,
+            glium::texture::MipmapsOption::NoMipmap,
+            SIZE,
+            SIZE,
+        )
+        .expect("failed to make texture");
+        let vertex_buffer = glium::VertexBuffer::new(
+            facade,
+            &[
+                Vert { pos: [-1.0, -1.0] },
+                Vert { pos: [3.0, -1.0] },
+                Vert { pos: [-1.0, 3.0] },
+            ],
+        )
+        .expect("failed to make vertex buffer");
+        let uniforms = glium::uniform! {
+            iResolution: [SIZE as f32, SIZE as f32, 1.0f32],
+        };
+        texture
+            .as_surface()
+            .draw(
+                &vertex_buffer,
+                glium::index::NoIndices(glium::index::PrimitiveType::TriangleStrip),
+                &program,
+                &uniforms,
+                &Default::default(),
+            )
+            .expect("failed to draw");
+
+        // opengl has the first row at the bottom
+        let pixels: Vec<Vec<(u8, u8, u8, u8)>> = texture.read();
+        RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+            let (r, g, b, a) = pixels[(SIZE - y - 1) as usize][x as usize];
+            image::Rgba([r, g, b, a])
+        })
+    }
+
+    // a headless context, the same way the compiler thread makes one, None if there's nothing to make it on
+    fn context() -> Option<(glutin::event_loop::EventLoop<()>, glium::HeadlessRenderer)> {
+        let event_loop = event_loop()?;
+        let context = glutin::ContextBuilder::new()
+            .build_headless(&event_loop, glutin::dpi::PhysicalSize::new(1, 1))
+            .ok()?;
+        let facade = glium::HeadlessRenderer::new(context).ok()?;
+        Some((event_loop, facade))
+    }
+
+    // tests don't run on the main thread, so the event loop has to be made on any thread
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn event_loop() -> Option<glutin::event_loop::EventLoop<()>> {
+        use glutin::platform::unix::EventLoopExtUnix;
+        // without a display it panics instead of failing
+        std::env::var_os("DISPLAY")
+            .or_else(|| std::env::var_os("WAYLAND_DISPLAY"))
+            .map(|_| glutin::event_loop::EventLoop::new_any_thread())
+    }
+
+    #[cfg(windows)]
+    fn event_loop() -> Option<glutin::event_loop::EventLoop<()>> {
+        use glutin::platform::windows::EventLoopExtWindows;
+        Some(glutin::event_loop::EventLoop::new_any_thread())
+    }
+
+    // macos only has event loops on the main thread
+    #[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+    fn event_loop() -> Option<glutin::event_loop::EventLoop<()>> {
+        None
+    }
+}
@@ -0,0 +1,473 @@
+use lsp_server::{ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+    Initialized, Notification as _, PublishDiagnostics,
+};
+use lsp_types::request::{
+    Completion, GotoDefinition, HoverRequest, Initialize, Request as _, Shutdown,
+};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, DiagnosticSeverity,
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    Documentation, GotoDefinitionParams, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, InitializeResult, Location, MarkupContent, MarkupKind, OneOf,
+    Position, PublishDiagnosticsParams, Range, ServerCapabilities, ServerInfo,
+    TextDocumentPositionParams, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TextDocumentSyncOptions, TextDocumentSyncSaveOptions, Url,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::args::Arguments;
+use crate::check;
+use crate::diagnostic::Severity;
+use crate::parser::Shadertoy;
+use crate::preprocess::{canonical, library_file};
+
+// what shadercrab adds to every pass, as name, declaration and description
+const BUILTINS: &[(&str, &str, &str)] = &[
+    ("iResolution", "uniform vec3 iResolution", "width, height and aspect ratio (y / x) of the output"),
+    ("iChannelResolution", "uniform vec3 iChannelResolution[4]", "resolution of each input, in the same way as iResolution"),
+    ("iMouse", "uniform vec4 iMouse", "xy: mouse position, changed when dragging with the left mouse button\n\nzw: mouse button states (0 is up, 1 is down)"),
+    ("iTime", "uniform float iTime", "seconds since the shader was loaded"),
+    ("iTimeDelta", "uniform float iTimeDelta", "seconds between the last two frames"),
+    ("iFrameRate", "uniform float iFrameRate", "frames drawn per second"),
+    ("iFrame", "uniform int iFrame", "current frame number"),
+    ("iSampleIndex", "uniform int iSampleIndex", "current sample when accumulating, 0 otherwise"),
+    ("iChannel0", "uniform sampler2D iChannel0", "input set by ichannel0 in the config"),
+    ("iChannel1", "uniform sampler2D iChannel1", "input set by ichannel1 in the config"),
+    ("iChannel2", "uniform sampler2D iChannel2", "input set by ichannel2 in the config"),
+    ("iChannel3", "uniform sampler2D iChannel3", "input set by ichannel3 in the config"),
+    ("mainImage", "void mainImage(out vec4 fragColor, in vec2 fragCoord)", "called for every pixel of the pass, with the pixel coordinate\n\npasses with more attachments get an output for each, before fragCoord"),
+    ("debugPrint", "void debugPrint(value)", "print an int, float or vector at the clicked pixel"),
+];
+
+// qualifiers that can go before the type of a top level definition
+const QUALIFIERS: &[&str] = &[
+    "const",
+    "uniform",
+    "highp",
+    "mediump",
+    "lowp",
+    "in",
+    "out",
+    "inout",
+    "flat",
+    "smooth",
+    "precise",
+    "invariant",
+];
+
+// something defined at the top level of a shader file
+struct Symbol {
+    name: String,
+    kind: CompletionItemKind,
+
+    // file it's in, the line, counting from 0, and the text on that line
+    path: PathBuf,
+    line: usize,
+    text: String,
+}
+
+// state of the language server
+struct Server<'a> {
+    // how to load the project, from the command line
+    args: &'a Arguments,
+
+    // the project, as last checked
+    shadertoy: Shadertoy,
+
+    // text of the files open in the editor, which can differ from the saved files
+    documents: HashMap<PathBuf, String>,
+
+    // files that currently have errors shown
+    published: Vec<PathBuf>,
+}
+
+/// run a language server for the project, over stdin and stdout
+/// returns when the editor asks it to exit
+pub fn lsp(args: &Arguments) {
+    let mut server = Server {
+        args,
+        shadertoy: Shadertoy::default(),
+        documents: HashMap::new(),
+        published: Vec::new(),
+    };
+
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock();
+    while let Ok(Some(message)) = Message::read(&mut input) {
+        match message {
+            Message::Request(request) => server.request(request),
+            Message::Notification(notification) if notification.method == "exit" => return,
+            Message::Notification(notification) => server.notification(notification),
+            Message::Response(_) => (),
+        }
+    }
+}
+
+impl<'a> Server<'a> {
+    // send a message to the editor, quits if it's gone
+    fn send(&self, message: impl Into<Message>) {
+        if message.into().write(&mut std::io::stdout().lock()).is_err() {
+            std::process::exit(1);
+        }
+    }
+
+    // answer a request, if it's one we know
+    fn request(&mut self, request: Request) {
+        match request.method.as_str() {
+            Initialize::METHOD => self.respond(request, |_, _: serde_json::Value| initialize()),
+            Shutdown::METHOD => self.respond(request, |_, _: ()| ()),
+            Completion::METHOD => self.respond(request, Self::completion),
+            HoverRequest::METHOD => self.respond(request, Self::hover),
+            GotoDefinition::METHOD => self.respond(request, Self::definition),
+            _ => self.send(Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unknown request {}", request.method),
+            )),
+        }
+    }
+
+    // answer a request with the given handler, or an error if the parameters don't fit
+    fn respond<P: DeserializeOwned, R: Serialize>(
+        &mut self,
+        request: Request,
+        handler: impl FnOnce(&mut Self, P) -> R,
+    ) {
+        let response = match serde_json::from_value(request.params) {
+            Ok(params) => Response::new_ok(request.id, handler(self, params)),
+            Err(reason) => Response::new_err(
+                request.id,
+                ErrorCode::InvalidParams as i32,
+                reason.to_string(),
+            ),
+        };
+        self.send(response);
+    }
+
+    // keep track of open files, and check the project when it's opened or saved
+    fn notification(&mut self, notification: Notification) {
+        let params = notification.params;
+        match notification.method.as_str() {
+            Initialized::METHOD | DidSaveTextDocument::METHOD => self.check(),
+            DidOpenTextDocument::METHOD => {
+                if let Ok(params) = serde_json::from_value::<DidOpenTextDocumentParams>(params) {
+                    if let Some(path) = file_path(&params.text_document.uri) {
+                        self.documents.insert(path, params.text_document.text);
+                    }
+                    self.check();
+                }
+            }
+            DidChangeTextDocument::METHOD => {
+                // the whole text is sent on every change
+                if let Ok(mut params) =
+                    serde_json::from_value::<DidChangeTextDocumentParams>(params)
+                {
+                    let path = file_path(&params.text_document.uri);
+                    if let (Some(path), Some(change)) = (path, params.content_changes.pop()) {
+                        self.documents.insert(path, change.text);
+                    }
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                if let Ok(params) = serde_json::from_value::<DidCloseTextDocumentParams>(params) {
+                    if let Some(path) = file_path(&params.text_document.uri) {
+                        self.documents.remove(&path);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // load and check the project, and show the errors in the editor
+    // this reads the saved files, like running it would
+    fn check(&mut self) {
+        // what's wrong with the project is in the diagnostics, nothing is printed
+        self.shadertoy = Shadertoy::new(
+            Path::new(&self.args.file_path),
+            &self.args.include_path,
+            &self.args.defines,
+            self.args.version,
+            self.args.message_format,
+            None,
+        );
+        let diagnostics = check::diagnostics(&self.shadertoy);
+
+        // errors without a file, or in the library, are shown on the config
+        let dir = self.shadertoy.dir();
+        let config = canonical(Path::new(&self.args.file_path));
+        let mut files: Vec<(PathBuf, Vec<lsp_types::Diagnostic>)> = Vec::new();
+        for diagnostic in diagnostics {
+            // errors about a whole file go on its first line
+            let (path, line, message) = match (&diagnostic.file, diagnostic.line) {
+                (Some(file), line) if dir.join(file).is_file() => (
+                    canonical(&dir.join(file)),
+                    line.unwrap_or(1),
+                    diagnostic.message.clone(),
+                ),
+                _ => (config.clone(), 1, diagnostic.to_string()),
+            };
+
+            // from the column, if there is one, to the end of the line
+            let mut range = line_range(line.saturating_sub(1));
+            range.start.character = diagnostic.column.unwrap_or(1).saturating_sub(1) as u32;
+
+            let diagnostic = lsp_types::Diagnostic {
+                range,
+                severity: Some(match diagnostic.severity {
+                    Severity::Error => DiagnosticSeverity::ERROR,
+                    Severity::Warning => DiagnosticSeverity::WARNING,
+                }),
+                source: Some("shadercrab".to_string()),
+                message,
+                ..Default::default()
+            };
+            match files.iter_mut().find(|x| x.0 == path) {
+                Some(x) => x.1.push(diagnostic),
+                None => files.push((path, vec![diagnostic])),
+            }
+        }
+
+        // files that had errors before, but don't now, need theirs cleared
+        let published = files.iter().map(|x| x.0.clone()).collect();
+        for path in std::mem::replace(&mut self.published, published) {
+            if !files.iter().any(|x| x.0 == path) {
+                files.push((path, Vec::new()));
+            }
+        }
+
+        for (path, diagnostics) in files {
+            if let Ok(uri) = Url::from_file_path(&path) {
+                self.send(Notification::new(
+                    PublishDiagnostics::METHOD.to_string(),
+                    PublishDiagnosticsParams {
+                        uri,
+                        diagnostics,
+                        version: None,
+                    },
+                ));
+            }
+        }
+    }
+
+    // builtins, defines, and everything defined in the shader files
+    fn completion(&mut self, _: CompletionParams) -> Vec<CompletionItem> {
+        let builtins = BUILTINS
+            .iter()
+            .map(|(name, declaration, description)| CompletionItem {
+                label: name.to_string(),
+                kind: Some(match declaration.contains('(') {
+                    true => CompletionItemKind::FUNCTION,
+                    false => CompletionItemKind::VARIABLE,
+                }),
+                detail: Some(declaration.to_string()),
+                documentation: Some(Documentation::String(description.to_string())),
+                ..Default::default()
+            });
+
+        let defines = self
+            .shadertoy
+            .defines()
+            .into_iter()
+            .map(|(name, value)| CompletionItem {
+                detail: Some(format!("#define {} {}", name, value)),
+                label: name,
+                kind: Some(CompletionItemKind::CONSTANT),
+                ..Default::default()
+            });
+
+        let symbols = self.symbols().into_iter().map(|x| CompletionItem {
+            label: x.name,
+            kind: Some(x.kind),
+            detail: Some(x.text),
+            ..Default::default()
+        });
+
+        builtins.chain(defines).chain(symbols).collect()
+    }
+
+    // what a builtin, define or symbol is
+    fn hover(&mut self, params: HoverParams) -> Option<Hover> {
+        let word = self.word_at(&params.text_document_position_params)?;
+
+        let value =
+            if let Some((_, declaration, description)) = BUILTINS.iter().find(|x| x.0 == word) {
+                format!("```glsl\n{}\n```\n{}", declaration, description)
+            } else if let Some((name, value)) =
+                self.shadertoy.defines().into_iter().find(|x| x.0 == word)
+            {
+                format!("```glsl\n#define {} {}\n```\nfrom the config", name, value)
+            } else {
+                let dir = self.shadertoy.dir();
+                let symbol = self.symbols().into_iter().find(|x| x.name == word)?;
+                let file = symbol.path.strip_prefix(&dir).unwrap_or(&symbol.path);
+                format!(
+                    "```glsl\n{}\n```\n{}:{}",
+                    symbol.text,
+                    file.display(),
+                    symbol.line + 1
+                )
+            };
+
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: None,
+        })
+    }
+
+    // where a symbol is defined, library files have no place to go to
+    fn definition(&mut self, params: GotoDefinitionParams) -> Vec<Location> {
+        let word = match self.word_at(&params.text_document_position_params) {
+            Some(x) => x,
+            None => return Vec::new(),
+        };
+
+        self.symbols()
+            .into_iter()
+            .filter(|x| x.name == word)
+            .filter_map(|x| {
+                Some(Location {
+                    uri: Url::from_file_path(canonical(&x.path)).ok()?,
+                    range: line_range(x.line),
+                })
+            })
+            .collect()
+    }
+
+    // everything defined at the top level of the shader files
+    fn symbols(&self) -> Vec<Symbol> {
+        let mut symbols = Vec::new();
+        for path in self.shadertoy.shader_files() {
+            let text = self.text(&path).unwrap_or_default();
+            for (line, text) in text.lines().enumerate() {
+                if let Some((name, kind)) = defined(text) {
+                    symbols.push(Symbol {
+                        name: name.to_string(),
+                        kind,
+                        path: path.clone(),
+                        line,
+                        text: text.trim().trim_end_matches('{').trim_end().to_string(),
+                    });
+                }
+            }
+        }
+        symbols
+    }
+
+    // text of a file, as open in the editor if it is
+    fn text(&self, path: &Path) -> Option<String> {
+        match library_file(path) {
+            Some(x) => Some(x.to_string()),
+            None => self
+                .documents
+                .get(&canonical(path))
+                .cloned()
+                .or_else(|| std::fs::read_to_string(path).ok()),
+        }
+    }
+
+    // identifier at a position in a file
+    fn word_at(&self, position: &TextDocumentPositionParams) -> Option<String> {
+        let text = self.text(&file_path(&position.text_document.uri)?)?;
+        let line = text.lines().nth(position.position.line as usize)?;
+        let chars = line.chars().collect::<Vec<char>>();
+        let at = (position.position.character as usize).min(chars.len());
+
+        let is_word = |c: &&char| c.is_alphanumeric() || **c == '_';
+        let start = at - chars[..at].iter().rev().take_while(is_word).count();
+        let end = at + chars[at..].iter().take_while(is_word).count();
+        (start < end).then(|| chars[start..end].iter().collect())
+    }
+}
+
+// what the server can do
+fn initialize() -> InitializeResult {
+    InitializeResult {
+        capabilities: ServerCapabilities {
+            text_document_sync: Some(TextDocumentSyncCapability::Options(
+                TextDocumentSyncOptions {
+                    open_close: Some(true),
+                    change: Some(TextDocumentSyncKind::FULL),
+                    save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                    ..Default::default()
+                },
+            )),
+            completion_provider: Some(CompletionOptions::default()),
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            definition_provider: Some(OneOf::Left(true)),
+            ..Default::default()
+        },
+        server_info: Some(ServerInfo {
+            name: "shadercrab".to_string(),
+            version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        }),
+    }
+}
+
+// path of a file uri, the same way the project has it
+fn file_path(uri: &Url) -> Option<PathBuf> {
+    uri.to_file_path().ok().map(|x| canonical(&x))
+}
+
+// an entire line, counting from 0
+fn line_range(line: usize) -> Range {
+    Range {
+        start: Position::new(line as u32, 0),
+        end: Position::new(line as u32 + 1, 0),
+    }
+}
+
+// name and kind of what a line defines at the top level, if anything
+// this goes by the shape of the line, which is enough for defines, structs, functions and globals
+fn defined(line: &str) -> Option<(&str, CompletionItemKind)> {
+    if let Some(directive) = line.trim_start().strip_prefix('#') {
+        let name = directive.trim_start().strip_prefix("define")?;
+        return match name.starts_with(char::is_whitespace) {
+            true => Some((identifier(name.trim_start())?, CompletionItemKind::CONSTANT)),
+            false => None,
+        };
+    }
+
+    // the top level isn't indented
+    if line.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    // the type, after any qualifiers
+    let mut rest = line;
+    let kind = loop {
+        let word = identifier(rest)?;
+        rest = rest[word.len()..].trim_start();
+        if !QUALIFIERS.contains(&word) {
+            break word;
+        }
+    };
+
+    let name = identifier(rest)?;
+    match (kind, rest[name.len()..].trim_start().chars().next()) {
+        ("return" | "else" | "case", _) => None,
+        ("struct", _) => Some((name, CompletionItemKind::STRUCT)),
+        (_, Some('(')) => Some((name, CompletionItemKind::FUNCTION)),
+        (_, Some('=' | ';' | '[' | ',')) => Some((name, CompletionItemKind::VARIABLE)),
+        _ => None,
+    }
+}
+
+// identifier at the start of the text, if there is one
+fn identifier(text: &str) -> Option<&str> {
+    let length = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(text.len());
+    match text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        true => Some(&text[..length]),
+        false => None,
+    }
+}
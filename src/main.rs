@@ -1,78 +1,114 @@
+pub mod args;
+pub mod bench;
 pub mod buffer;
+pub mod cache;
+pub mod check;
+pub mod clock;
+pub mod compiler;
+pub mod debug;
+pub mod debug_print;
+pub mod diagnostic;
 pub mod drawer;
+pub mod graph;
+pub mod inspector;
+pub mod library;
+pub mod lsp;
+pub mod nan;
+pub mod overlay;
 pub mod parser;
+pub mod preprocess;
 pub mod program;
+pub mod render;
 
 use glium::glutin;
-use glutin::event::{ElementState, Event, MouseButton, VirtualKeyCode, WindowEvent};
+use glutin::event::{ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
+use std::time::{Duration, Instant};
 
+use crate::args::*;
+use crate::buffer::*;
+use crate::clock::*;
+use crate::diagnostic::{message, Diagnostic, Severity};
 use crate::drawer::*;
 use crate::parser::*;
-use crate::program::*;
 
 fn main() {
     // figure out what shader to load
-    let (file_path, render_scale): (String, f32) = match &std::env::args().collect::<Vec<String>>()
-        [..]
-    {
-        [_, x] => (x.clone(), 1.0),
-        [_, x, p, y] if p == "-s" || p == "--scale" => (
-            x.clone(),
-            y.parse::<f32>().map_or_else(
-                |_| {
-                    println!("Could not parse scale as a float");
-                    std::process::exit(0);
-                },
-                |x| x,
-            ),
-        ),
-        _ => {
+    let args = match Arguments::parse(&std::env::args().skip(1).collect::<Vec<String>>()) {
+        Some(x) => x,
+        None => {
             // no valid arguments, show the help menu
-            println!("Shadercrab {}", env!("CARGO_PKG_VERSION"));
-            println!("A simple shadertoy emulator");
-            println!("Usage:");
-            println!("shadercrab [path] [-s|--scale render scale]");
-            println!("	path: path to the shader file to use");
-            println!("  render scale: what resolution to render at compared to window resolution");
-            println!("");
-            println!("This opens a window that shows the shader");
-            println!("The shader is reloaded when the file is modified, or the r key is pressed");
-            println!("Any shader errors are printed to the terminal");
-            println!("");
-            println!("Shader format:");
-            println!("Shaders are in glsl, and need the function");
-            println!("	mainImage(out vec4 fragColor, in vec2 fragCoord)");
-            println!("where");
-            println!("	fragColor: output color for the pixel, in sRGB color space");
-            println!("	fragCoord: the pixel coordinate, with bottom left at (0, 0) and top right at (width, height)");
-            println!("	           width and height are the width and height of the window");
-            println!("");
-            println!("The following constants are also defined:");
-            println!("	float iTime: seconds since the shader was loaded");
-            println!("	int iFrame: current frame number");
-            println!("	vec3 iResolution: width, height and aspect ratio (y / x) of the window");
-            println!(
-                "	vec4 iMouse: xy: mouse position, changed when dragging with the left mouse button"
-            );
-            println!("	             zw: mouse button states (0 is up, 1 is down)");
+            Arguments::print_help();
             return;
         }
     };
+    // run the language server if asked, before anything is printed, as that would go to the editor
+    if args.command == Command::Lsp {
+        lsp::lsp(&args);
+        return;
+    }
+
+    let file_path = args.file_path.clone();
+    let render_scale = args.render_scale;
+    let format = args.message_format;
 
-    // time at which the file got modified
     // if it's not a valid file, show why we crashed
-    let mut time_stamp = {
-        match std::fs::metadata(&file_path) {
-            Ok(x) => x
-                .modified()
-                .expect("Failed to access file access timestamp"),
-            Err(reason) => {
-                println!("Failed to open file: {:?}", reason);
-                return;
-            }
-        }
+    if let Err(reason) = std::fs::metadata(&file_path) {
+        let text = format!("failed to open file: {}", reason);
+        Diagnostic::in_file(file_path, None, Severity::Error, text).print(format);
+        std::process::exit(1);
+    }
+
+    // compiled programs are kept here
+    let cache = match args.cache {
+        true => cache::Cache::new(),
+        false => None,
     };
 
+    // load the program
+    let mut shadertoy = Shadertoy::new(
+        &std::path::PathBuf::from(&file_path),
+        &args.include_path,
+        &args.defines,
+        args.version,
+        args.message_format,
+        cache.clone(),
+    );
+
+    // check the shaders compile if asked, this needs no window or gpu
+    // it shows what's wrong with the files along with the errors in the passes
+    if args.command != Command::Check {
+        shadertoy.print_errors();
+    }
+    if args.command == Command::Check {
+        std::process::exit(if check::check(&shadertoy, args.message_format) {
+            0
+        } else {
+            1
+        });
+    }
+
+    // accumulating from the arguments takes priority over the config
+    let accumulate = args.accumulate.or_else(|| shadertoy.accumulate());
+
+    // benchmark if asked
+    if args.command == Command::Bench {
+        bench::bench(&args, &shadertoy);
+        return;
+    }
+
+    // render offline if we have somewhere to write to
+    if let Some(output) = &args.output {
+        render::render(&args, &shadertoy, output);
+        return;
+    }
+
+    // how to pace frames, the arguments take priority over the config
+    let fps = args
+        .fps
+        .or_else(|| shadertoy.fps())
+        .unwrap_or(FrameRate::Capped(60.0));
+    let vsync = args.vsync.or_else(|| shadertoy.vsync()).unwrap_or(true);
+
     // start up the event loop
     let event_loop = glutin::event_loop::EventLoop::new();
     let wb = glutin::window::WindowBuilder::new()
@@ -82,35 +118,60 @@ fn main() {
             file_path
         ))
         .with_inner_size(glutin::dpi::PhysicalSize::new(800, 450));
-    let cb = glutin::ContextBuilder::new().with_vsync(true);
+    let cb = glutin::ContextBuilder::new().with_vsync(vsync);
     let display = glium::Display::new(wb, cb, &event_loop).unwrap();
 
-    println!(
-        "GPU: {}\nVendor: {}\nOpenGL version: {}",
-        display.get_opengl_renderer_string(),
-        display.get_opengl_vendor_string(),
-        display.get_opengl_version_string()
+    message(
+        format,
+        &format!(
+            "GPU: {}\nVendor: {}\nOpenGL version: {}",
+            display.get_opengl_renderer_string(),
+            display.get_opengl_vendor_string(),
+            display.get_opengl_version_string()
+        ),
     );
 
     // current screen size
     let resolution = display.get_framebuffer_dimensions();
 
     // the actual drawing manager
-    let mut drawer = Drawer::new(&display, resolution.0, resolution.1, render_scale);
-
-    // load the program
-    // mutable so we can reload later
-    //let program = load_program(&display, &file_path);
-    let shadertoy = Shadertoy::new(&std::path::PathBuf::from(&file_path));
+    let mut drawer = Drawer::new(
+        &display,
+        resolution.0,
+        resolution.1,
+        render_scale,
+        accumulate,
+    );
+    drawer.debug.range = args.range;
+    drawer.nan_check.enabled = args.check_nan;
+    drawer.message_format = format;
 
-    // apply it
+    // apply the program
     shadertoy.load_shaders(&display, &mut drawer);
 
-    // time since program start
-    let mut start_time = std::time::Instant::now();
+    // compiles the shaders after this, so the window doesn't freeze while it does
+    // without it, they're compiled here
+    let mut compiler = compiler::Compiler::new(&event_loop, args.message_format, cache);
+
+    // iTime and iFrame
+    let mut clock = Clock::new(args.start_time, args.speed, args.paused, args.fixed);
 
-    // current frame
-    let mut frame = 0;
+    // whether to draw, even if paused
+    let mut redraw = false;
+
+    // whether to show the last image again, for when only the overlay or inspector changed
+    let mut repaint = false;
+
+    // whether to reload the shader, even if none of its files changed
+    let mut reload = false;
+
+    // passes whose last change isn't running, as another pass in its job failed to compile
+    // they're compiled again with the next edit
+    let mut failed_passes = Vec::new();
+
+    // whether the passes running are from before a reload that failed to compile
+    // everything is reloaded again until one works, as the passes may not match anymore
+    let mut stale = false;
 
     // mouse position and status
     let mut mouse_pos = (0, 0);
@@ -120,11 +181,23 @@ fn main() {
     let mut focus = false;
 
     // draw once to show it
-    drawer.draw(&display, 0.0, frame, mouse_pos, mouse_stat, render_scale);
+    drawer.draw(
+        &display,
+        &FrameInfo {
+            time: clock.time(),
+            time_delta: clock.delta(),
+            frame_rate: clock.frame_rate(),
+            frame: clock.frame(),
+            ..Default::default()
+        },
+        render_scale,
+    );
+
+    // when the next frame should be drawn
+    let mut next_frame = Instant::now();
 
     // and run the event loop
     event_loop.run(move |event, _, control_flow| {
-        // TODO reload program if the file changed
         // close if needed
         match event {
             Event::WindowEvent { event, .. } => match event {
@@ -134,6 +207,10 @@ fn main() {
                 }
                 // resized
                 WindowEvent::Resized(s) => {
+                    // show the last frame at the new size, even if paused
+                    // drawing would run the passes again for the same frame, and step simulations
+                    repaint = true;
+
                     // rename the window to include the size
                     display.gl_window().window().set_title(&format!(
                         "Shadercrab {} - {}x{} - {}",
@@ -147,10 +224,15 @@ fn main() {
                 WindowEvent::Focused(f) => focus = f,
                 // check mouse position
                 WindowEvent::CursorMoved { position, .. } => {
-                    mouse_pos = if mouse_stat.0 {
-                        (position.x as u32, position.y as u32)
-                    } else {
-                        mouse_pos
+                    // the inspector follows the cursor
+                    drawer.inspector.cursor = (position.x, position.y);
+                    repaint |= drawer.inspector.active;
+
+                    // but the shader only sees it when dragging
+                    // and draws again, at the same time and frame if paused
+                    if mouse_stat.0 {
+                        mouse_pos = (position.x as u32, position.y as u32);
+                        redraw = true;
                     }
                 }
                 // check mouse buttons
@@ -158,64 +240,259 @@ fn main() {
                     state,
                     button: MouseButton::Left,
                     ..
-                } => mouse_stat.0 = state == ElementState::Pressed,
+                } => {
+                    mouse_stat.0 = state == ElementState::Pressed;
+                    redraw = true;
+                }
                 WindowEvent::MouseInput {
                     state,
                     button: MouseButton::Right,
                     ..
-                } => mouse_stat.1 = state == ElementState::Pressed,
+                } => {
+                    mouse_stat.1 = state == ElementState::Pressed;
+                    redraw = true;
+                }
+                // print what the shader prints with debugPrint at the clicked pixel
+                WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    button: MouseButton::Middle,
+                    ..
+                } => {
+                    drawer.printer.request = Some(drawer.inspector.cursor);
+                    repaint = true;
+                }
                 // check if we need to reload
-                WindowEvent::KeyboardInput { input, .. } => {
+                WindowEvent::KeyboardInput { input, .. }
                     if input.virtual_keycode == Some(VirtualKeyCode::R)
                         && input.state == ElementState::Released
-                        && focus
-                    {
-                        println!("Reloaded shader");
-                        //drawer.buffers[0].program = load_program(&display, &file_path);
-                        // reset the time as well
-                        start_time = std::time::Instant::now();
-                        // reset the frame
-                        frame = 0;
-                        // reset the mouse
-                        mouse_pos = (0, 0);
+                        && focus =>
+                {
+                    reload = true;
+                }
+                // inspect while holding i
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(VirtualKeyCode::I),
+                            state,
+                            ..
+                        },
+                    ..
+                } if focus => {
+                    drawer.inspector.active = state == ElementState::Pressed;
+                    repaint = true;
+                }
+                // time controls
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(key),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } if focus => {
+                    match key {
+                        VirtualKeyCode::Space => clock.toggle_pause(),
+                        VirtualKeyCode::Period => clock.step(),
+                        VirtualKeyCode::Left => clock.skip(-args.skip),
+                        VirtualKeyCode::Right => clock.skip(args.skip),
+                        VirtualKeyCode::Up => clock.set_speed(clock.speed() * 2.0),
+                        VirtualKeyCode::Down => clock.set_speed(clock.speed() * 0.5),
+                        VirtualKeyCode::F1 => {
+                            drawer.overlay.visible = !drawer.overlay.visible;
+                            repaint = true;
+                            return;
+                        }
+                        VirtualKeyCode::F2 => {
+                            drawer.debug.view = drawer.debug.view.next();
+                            repaint = true;
+                            return;
+                        }
+                        VirtualKeyCode::F3 => {
+                            drawer.debug.remap = drawer.debug.remap.next();
+                            repaint = true;
+                            return;
+                        }
+                        VirtualKeyCode::F4 => {
+                            drawer.nan_check.enabled = !drawer.nan_check.enabled;
+                            drawer.nan_check.found = None;
+                            // look at what's shown now, drawing it again would step simulations when paused
+                            if drawer.nan_check.enabled {
+                                drawer.check_finite(&display, clock.frame());
+                            }
+                            repaint = true;
+                            return;
+                        }
+                        // pass to show solo, and to inspect
+                        VirtualKeyCode::Key1
+                        | VirtualKeyCode::Key2
+                        | VirtualKeyCode::Key3
+                        | VirtualKeyCode::Key4
+                        | VirtualKeyCode::Key5
+                        | VirtualKeyCode::Key6
+                        | VirtualKeyCode::Key7
+                        | VirtualKeyCode::Key8
+                        | VirtualKeyCode::Key9 => {
+                            let pass = key as usize - VirtualKeyCode::Key1 as usize;
+                            drawer.debug.pass = pass.min(drawer.buffers.len() - 1);
+                            repaint = true;
+                            return;
+                        }
+                        // defines from the config turned on and off
+                        _ => {
+                            if let Some(define) = shadertoy.toggle(key) {
+                                let sources = shadertoy.toggled_sources(&define);
+                                match &mut compiler {
+                                    // the passes running may not match the ones toggled
+                                    _ if stale => reload = true,
+                                    Some(compiler) => compiler.compile(sources, false),
+                                    None => {
+                                        match shadertoy.recompile(&display, &mut drawer, sources) {
+                                            Ok(()) => {
+                                                drawer.reset_accumulation();
+                                                redraw = true;
+                                            }
+                                            Err(passes) => failed_passes.extend(passes),
+                                        }
+                                    }
+                                }
+                            }
+                            return;
+                        }
                     }
+
+                    // show what happened
+                    let text = format!(
+                        "Time: {:.3}s, frame: {}, speed: {}x{}",
+                        clock.time(),
+                        clock.frame(),
+                        clock.speed(),
+                        if clock.paused { ", paused" } else { "" }
+                    );
+                    message(format, &text);
+                    redraw = true;
                 }
                 _ => (),
             },
             Event::NewEvents(glutin::event::StartCause::ResumeTimeReached { .. })
-            | Event::NewEvents(glutin::event::StartCause::Init) => {
-                // check if the shader was edited
-                /*let new_time_stamp = std::fs::metadata(&file_path).unwrap().modified().unwrap();
-                if new_time_stamp != time_stamp {
-                    // reload if it was
-                    println!("Reloaded shader");
-                    drawer.buffers[0].program = load_program(&display, &file_path);
-                    // reset the time as well
-                    start_time = std::time::Instant::now();
-                    // reset the frame
-                    frame = 0;
+            | Event::NewEvents(glutin::event::StartCause::Init)
+            | Event::NewEvents(glutin::event::StartCause::Poll) => {
+                // check if the shader, or anything it includes, was edited
+                let mut reloaded = false;
+                if reload || shadertoy.modified() {
+                    // reloading with r always starts over, an edit only recompiles the passes it changed
+                    let sources = shadertoy.reload();
+                    shadertoy.print_errors();
+                    match sources.filter(|_| !reload && !stale) {
+                        // the other passes keep what they drew
+                        Some(mut sources) => {
+                            for (_, source) in &sources {
+                                message(format, &format!("Reloaded {}", source.pass));
+                            }
+                            // with the passes that failed before
+                            let retry = shadertoy
+                                .program_sources()
+                                .into_iter()
+                                .filter(|(i, _)| failed_passes.contains(i))
+                                .filter(|(i, _)| !sources.iter().any(|x| x.0 == *i))
+                                .collect::<Vec<_>>();
+                            sources.extend(retry);
+                            failed_passes.clear();
+                            match &mut compiler {
+                                Some(compiler) if !sources.is_empty() => {
+                                    compiler.compile(sources, false)
+                                }
+                                Some(_) => (),
+                                None => match shadertoy.recompile(&display, &mut drawer, sources) {
+                                    Ok(()) => {
+                                        drawer.reset_accumulation();
+                                        redraw = true;
+                                    }
+                                    Err(passes) => failed_passes.extend(passes),
+                                },
+                            }
+                        }
+                        None => match &mut compiler {
+                            // the old passes keep running until the new ones are compiled
+                            Some(compiler) => compiler.compile(shadertoy.program_sources(), true),
+                            None => {
+                                reloaded = shadertoy.reload_shaders(&display, &mut drawer);
+                                stale = !reloaded;
+                                failed_passes.clear();
+                            }
+                        },
+                    }
+                    reload = false;
+                }
+
+                // swap in the compiled passes, all at once
+                match compiler.as_mut().and_then(|x| x.finished(&display)) {
+                    Some(compiler::Finished::Reload(programs)) => {
+                        let programs = programs.into_iter().map(Some).collect();
+                        shadertoy.set_programs(&display, &mut drawer, programs);
+                        reloaded = true;
+                        stale = false;
+                        failed_passes.clear();
+                    }
+                    Some(compiler::Finished::Recompile(programs)) => {
+                        shadertoy.swap_programs(&mut drawer, programs);
+                        drawer.reset_accumulation();
+                        redraw = true;
+                    }
+                    // the previous passes keep running
+                    Some(compiler::Finished::Failed { reload: true, .. }) => stale = true,
+                    Some(compiler::Finished::Failed { passes, .. }) => failed_passes.extend(passes),
+                    None => (),
+                }
+
+                if reloaded {
+                    message(format, "Reloaded shader");
+                    // reset the time and frame as well
+                    clock.reset();
                     // reset the mouse
                     mouse_pos = (0, 0);
-                    // and prevent continuous reloading
-                    time_stamp = new_time_stamp;
-                }*/
+                    // and start accumulating again
+                    drawer.reset_accumulation();
+                    // and look for NaN and Inf again
+                    drawer.nan_check.found = None;
+                    redraw = true;
+                }
 
-                // increment the frame
-                frame += 1;
+                // go to the next frame, if not paused
+                let tick = clock.tick();
+                let info = FrameInfo {
+                    time: clock.time(),
+                    time_delta: clock.delta(),
+                    frame_rate: clock.frame_rate(),
+                    frame: clock.frame(),
+                    mouse_position: mouse_pos,
+                    mouse_input: mouse_stat,
+                    ..Default::default()
+                };
 
                 // we're reached the end of the frame, redraw
-                drawer.draw(
-                    &display,
-                    start_time.elapsed().as_secs_f32(),
-                    frame,
-                    mouse_pos,
-                    mouse_stat,
-                    render_scale,
-                );
-                // and request a redraw, at 60 fps
-                *control_flow = glutin::event_loop::ControlFlow::WaitUntil(
-                    std::time::Instant::now() + std::time::Duration::from_secs_f32(1.0 / 60.0),
-                );
+                if tick || redraw {
+                    drawer.draw(&display, &info, render_scale);
+                } else if repaint {
+                    // only show it again, so paused buffers don't change
+                    drawer.present(&display, &info, render_scale);
+                }
+                redraw = false;
+                repaint = false;
+
+                // and request a redraw
+                *control_flow = match fps {
+                    FrameRate::Capped(fps) => {
+                        // count from the last deadline instead of from now, so the frame rate doesn't drift
+                        // unless we're too far behind to catch up
+                        next_frame += Duration::from_secs_f32(1.0 / fps);
+                        next_frame = next_frame.max(Instant::now());
+                        glutin::event_loop::ControlFlow::WaitUntil(next_frame)
+                    }
+                    // as fast as possible
+                    FrameRate::Uncapped => glutin::event_loop::ControlFlow::Poll,
+                };
             }
             _ => (),
         }
@@ -0,0 +1,248 @@
+use glium::Surface;
+use std::collections::HashMap;
+
+use crate::buffer::Vert;
+use crate::debug::viewport;
+
+// how many texels in each direction are combined in a single reduction step
+const REDUCTION: u32 = 4;
+
+// most sizes to keep textures to reduce into for, sizes from resizing are dropped after this
+const MAX_CHAINS: usize = 16;
+
+/// where NaN or Inf was first found
+pub struct NonFinite {
+    pub frame: i32,
+    pub pass: String,
+    pub nan: bool,
+    pub inf: bool,
+}
+
+impl std::fmt::Display for NonFinite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match (self.nan, self.inf) {
+            (true, true) => "NaN and Inf",
+            (true, false) => "NaN",
+            _ => "Inf",
+        };
+        write!(f, "{} in pass {} at frame {}", kind, self.pass, self.frame)
+    }
+}
+
+/// finds NaN and Inf in the output of passes, and highlights them
+pub struct NanCheck {
+    // whether to check every frame
+    pub enabled: bool,
+
+    // first NaN or Inf found since the last reset
+    pub found: Option<NonFinite>,
+
+    // program that finds the largest value in a block of texels
+    reduce_program: glium::Program,
+
+    // program that draws NaN and Inf in a loud colour
+    highlight_program: glium::Program,
+
+    // textures to reduce into for every size of pass, each smaller than the last until 1x1
+    chains: HashMap<(u32, u32), Vec<glium::Texture2d>>,
+}
+
+impl NanCheck {
+    pub fn new(display: &glium::Display) -> Self {
+        let vertex_shader = "
+            #version 140
+
+            in vec2 pos;
+            out vec2 vpos;
+
+            void main() {
+                gl_Position = vec4(pos, 0.0, 1.0);
+                vpos = pos * 0.5 + 0.5;
+            }
+            ";
+
+        let reduce_program = glium::Program::from_source(
+            display,
+            vertex_shader,
+            "
+            #version 140
+
+            out vec4 fragcol;
+
+            uniform sampler2D image;
+
+            // whether the image is a pass, instead of the previous step
+            uniform bool first;
+
+            // texels in each direction to combine
+            uniform int reduction;
+
+            void main() {
+                ivec2 size = textureSize(image, 0);
+                ivec2 base = ivec2(gl_FragCoord.xy) * reduction;
+
+                // r is whether there is a NaN, g whether there is an Inf
+                vec2 found = vec2(0.0);
+                for (int y = 0; y < reduction; y++) {
+                    for (int x = 0; x < reduction; x++) {
+                        ivec2 texel = base + ivec2(x, y);
+                        if (any(greaterThanEqual(texel, size))) continue;
+
+                        vec4 value = texelFetch(image, texel, 0);
+                        found = max(found, first ? vec2(any(isnan(value)), any(isinf(value))) : value.rg);
+                    }
+                }
+
+                fragcol = vec4(found, 0.0, 1.0);
+            }
+            ",
+            None,
+        )
+        .expect("failed to make NaN reduction program");
+
+        // outputs_srgb doesn't matter here, as the colors are fully saturated
+        let highlight_program = glium::Program::from_source(
+            display,
+            vertex_shader,
+            "
+            #version 140
+
+            in vec2 vpos;
+            out vec4 fragcol;
+
+            uniform sampler2D image;
+
+            void main() {
+                vec4 value = texture(image, vpos);
+
+                // NaN is magenta, Inf is cyan, anything else is left alone
+                if (any(isnan(value))) {
+                    fragcol = vec4(1.0, 0.0, 1.0, 1.0);
+                } else if (any(isinf(value))) {
+                    fragcol = vec4(0.0, 1.0, 1.0, 1.0);
+                } else {
+                    discard;
+                }
+            }
+            ",
+            None,
+        )
+        .expect("failed to make NaN highlight program");
+
+        Self {
+            enabled: false,
+            found: None,
+            reduce_program,
+            highlight_program,
+            chains: HashMap::new(),
+        }
+    }
+
+    /// whether the texture has any NaN and any Inf in it
+    /// this waits for the gpu to finish drawing it
+    pub fn check(
+        &mut self,
+        display: &glium::Display,
+        vertex_buffer: &glium::VertexBuffer<Vert>,
+        texture: &glium::Texture2d,
+    ) -> (bool, bool) {
+        // make the textures to reduce into, if there are none for this size yet
+        let size = texture.dimensions();
+        if !self.chains.contains_key(&size) && self.chains.len() >= MAX_CHAINS {
+            self.chains.clear();
+        }
+        let chain = self
+            .chains
+            .entry(size)
+            .or_insert_with(|| reduction_chain(display, size));
+
+        // reduce until there's a single texel left
+        for i in 0..chain.len() {
+            let image = if i == 0 { texture } else { &chain[i - 1] };
+            let uniforms = glium::uniform! {
+                image: image,
+                first: i == 0,
+                reduction: REDUCTION as i32,
+            };
+            chain[i]
+                .as_surface()
+                .draw(
+                    vertex_buffer,
+                    glium::index::NoIndices(glium::index::PrimitiveType::TriangleStrip),
+                    &self.reduce_program,
+                    &uniforms,
+                    &Default::default(),
+                )
+                .unwrap();
+        }
+
+        // and read it back
+        let pixels: Vec<Vec<(f32, f32, f32, f32)>> = chain[chain.len() - 1]
+            .main_level()
+            .first_layer()
+            .into_image(None)
+            .expect("failed to get texture image")
+            .raw_read(&glium::Rect {
+                left: 0,
+                bottom: 0,
+                width: 1,
+                height: 1,
+            });
+        let (nan, inf, _, _) = pixels[0][0];
+        (nan > 0.0, inf > 0.0)
+    }
+
+    /// draw NaN and Inf in the texture in a loud colour, in the given rect
+    pub fn highlight<S: Surface>(
+        &self,
+        target: &mut S,
+        vertex_buffer: &glium::VertexBuffer<Vert>,
+        texture: &glium::Texture2d,
+        rect: [f32; 4],
+    ) {
+        let uniforms = glium::uniform! {
+            image: texture
+                .sampled()
+                .wrap_function(glium::uniforms::SamplerWrapFunction::Clamp)
+                .minify_filter(glium::uniforms::MinifySamplerFilter::Nearest)
+                .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest),
+        };
+
+        target
+            .draw(
+                vertex_buffer,
+                glium::index::NoIndices(glium::index::PrimitiveType::TriangleStrip),
+                &self.highlight_program,
+                &uniforms,
+                &glium::DrawParameters {
+                    viewport: Some(viewport(target, rect)),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+    }
+}
+
+// textures to reduce a texture of the given size into, each smaller than the last until 1x1
+// always at least one step, so the pass itself is never read back
+fn reduction_chain(display: &glium::Display, size: (u32, u32)) -> Vec<glium::Texture2d> {
+    let (mut width, mut height) = size;
+    let mut chain = Vec::new();
+    loop {
+        width = width.div_ceil(REDUCTION).max(1);
+        height = height.div_ceil(REDUCTION).max(1);
+        chain.push(
+            glium::Texture2d::empty_with_format(
+                display,
+                glium::texture::UncompressedFloatFormat::F32F32F32F32,
+                glium::texture::MipmapsOption::NoMipmap,
+                width,
+                height,
+            )
+            .expect("failed to make texture"),
+        );
+        if width == 1 && height == 1 {
+            return chain;
+        }
+    }
+}
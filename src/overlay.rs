@@ -0,0 +1,325 @@
+use glium::Surface;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+// built in font, 6x10 pixels per character, 16 characters per row, starting at the space
+// this is the misc-fixed 6x10 font from X11, which is in the public domain
+const FONT: &[u8] = include_bytes!("font.raw");
+const FONT_WIDTH: u32 = 96;
+const FONT_HEIGHT: u32 = 60;
+const GLYPH_WIDTH: u32 = 6;
+const GLYPH_HEIGHT: u32 = 10;
+
+// how big a font pixel is on screen
+const TEXT_SCALE: f32 = 2.0;
+
+// how many frames to show in the frame time graph
+const GRAPH_FRAMES: usize = 120;
+
+// frame time at the top of the graph, in milliseconds
+const GRAPH_MAX_TIME: f32 = 50.0;
+
+// size of the graph, in screen pixels
+const GRAPH_WIDTH: f32 = 240.0;
+const GRAPH_HEIGHT: f32 = 60.0;
+
+// space between the edge of the screen and the overlay
+const MARGIN: f32 = 8.0;
+
+// vertex for text and rectangles
+#[derive(Copy, Clone)]
+struct OverlayVert {
+    // position in screen pixels, from the top left
+    pos: [f32; 2],
+    // pixel in the font texture, or negative for solid color
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+glium::implement_vertex!(OverlayVert, pos, uv, color);
+
+/// text and graphs drawn on top of the shader
+pub struct Overlay {
+    // whether to draw it
+    pub visible: bool,
+
+    // program to draw text and rectangles
+    program: glium::Program,
+
+    // font texture
+    font: glium::Texture2d,
+
+    // last frame times, in milliseconds
+    frame_times: VecDeque<f32>,
+
+    // when the last frame was drawn
+    last_frame: Instant,
+}
+
+impl Overlay {
+    pub fn new(display: &glium::Display) -> Self {
+        // outputs_srgb, same as the main image, so colors aren't changed
+        let program = glium::program::Program::new(
+            display,
+            glium::program::ProgramCreationInput::SourceCode {
+                vertex_shader: "
+            #version 140
+
+            in vec2 pos;
+            in vec2 uv;
+            in vec4 color;
+
+            out vec2 vuv;
+            out vec4 vcolor;
+
+            // size of the screen, to go from pixels to gl coordinates
+            uniform vec2 resolution;
+
+            void main() {
+                // pixels go from the top left, gl goes from the bottom left
+                gl_Position = vec4(pos / resolution * vec2(2.0, -2.0) + vec2(-1.0, 1.0), 0.0, 1.0);
+                vuv = uv;
+                vcolor = color;
+            }
+            ",
+                tessellation_control_shader: None,
+                tessellation_evaluation_shader: None,
+                geometry_shader: None,
+                fragment_shader: "
+            #version 140
+
+            in vec2 vuv;
+            in vec4 vcolor;
+            out vec4 fragcol;
+
+            uniform sampler2D font;
+
+            void main() {
+                // solid if there is no texture coordinate, otherwise use the font
+                float alpha = vuv.x < 0.0 ? 1.0 : texelFetch(font, ivec2(vuv), 0).r;
+                fragcol = vcolor * vec4(1.0, 1.0, 1.0, alpha);
+            }
+            ",
+                transform_feedback_varyings: None,
+                outputs_srgb: true,
+                uses_point_size: false,
+            },
+        )
+        .expect("failed to make overlay program");
+
+        // unpack the font, it's one bit per pixel
+        let pixels = (0..FONT_WIDTH * FONT_HEIGHT)
+            .map(|i| {
+                let byte = FONT[(i / 8) as usize];
+                if byte >> (7 - i % 8) & 1 == 1 {
+                    255u8
+                } else {
+                    0u8
+                }
+            })
+            .collect::<Vec<u8>>();
+        let font = glium::Texture2d::with_format(
+            display,
+            glium::texture::RawImage2d {
+                data: std::borrow::Cow::Owned(pixels),
+                width: FONT_WIDTH,
+                height: FONT_HEIGHT,
+                format: glium::texture::ClientFormat::U8,
+            },
+            glium::texture::UncompressedFloatFormat::U8,
+            glium::texture::MipmapsOption::NoMipmap,
+        )
+        .expect("failed to make font texture");
+
+        Self {
+            visible: false,
+            program,
+            font,
+            frame_times: VecDeque::with_capacity(GRAPH_FRAMES),
+            last_frame: Instant::now(),
+        }
+    }
+
+    /// draw the given lines of text and the frame time graph
+    pub fn draw<S: Surface>(&mut self, display: &glium::Display, target: &mut S, lines: &[String]) {
+        // measure how long the last frame took
+        self.frame_times
+            .push_back(self.last_frame.elapsed().as_secs_f32() * 1000.0);
+        self.last_frame = Instant::now();
+        if self.frame_times.len() > GRAPH_FRAMES {
+            self.frame_times.pop_front();
+        }
+
+        let mut vertices = Vec::new();
+
+        // background, so the text is readable
+        let text_size = text_size(lines);
+        rectangle(
+            &mut vertices,
+            [0.0, 0.0],
+            [
+                text_size[0].max(GRAPH_WIDTH) + MARGIN * 2.0,
+                text_size[1] + GRAPH_HEIGHT + MARGIN * 3.0,
+            ],
+            [0.0, 0.0, 0.0, 0.6],
+        );
+
+        // text
+        text_lines(&mut vertices, [MARGIN, MARGIN], lines);
+
+        // graph, one bar per frame, with a line at 60 fps
+        let graph_top = text_size[1] + MARGIN * 2.0;
+        let bar_width = GRAPH_WIDTH / GRAPH_FRAMES as f32;
+        for (i, time) in self.frame_times.iter().enumerate() {
+            let height = (time / GRAPH_MAX_TIME).min(1.0) * GRAPH_HEIGHT;
+            rectangle(
+                &mut vertices,
+                [
+                    MARGIN + i as f32 * bar_width,
+                    graph_top + GRAPH_HEIGHT - height,
+                ],
+                [bar_width, height],
+                [0.3, 0.9, 0.4, 1.0],
+            );
+        }
+        rectangle(
+            &mut vertices,
+            [
+                MARGIN,
+                graph_top + GRAPH_HEIGHT * (1.0 - 1000.0 / 60.0 / GRAPH_MAX_TIME),
+            ],
+            [GRAPH_WIDTH, 1.0],
+            [1.0, 1.0, 1.0, 0.5],
+        );
+
+        self.draw_vertices(display, target, &vertices);
+    }
+
+    /// draw lines of text with a background, with the top left at the given position in pixels
+    /// the position is moved so the text stays on screen
+    pub fn draw_text<S: Surface>(
+        &self,
+        display: &glium::Display,
+        target: &mut S,
+        pos: [f32; 2],
+        lines: &[String],
+    ) {
+        let (width, height) = target.get_dimensions();
+        let size = text_size(lines);
+        let size = [size[0] + MARGIN * 2.0, size[1] + MARGIN * 2.0];
+        let pos = [
+            pos[0].min(width as f32 - size[0]).max(0.0),
+            pos[1].min(height as f32 - size[1]).max(0.0),
+        ];
+
+        let mut vertices = Vec::new();
+        rectangle(&mut vertices, pos, size, [0.0, 0.0, 0.0, 0.6]);
+        text_lines(&mut vertices, [pos[0] + MARGIN, pos[1] + MARGIN], lines);
+        self.draw_vertices(display, target, &vertices);
+    }
+
+    // draw text and rectangles
+    fn draw_vertices<S: Surface>(
+        &self,
+        display: &glium::Display,
+        target: &mut S,
+        vertices: &[OverlayVert],
+    ) {
+        let vertex_buffer =
+            glium::VertexBuffer::new(display, vertices).expect("failed to make vertex buffer");
+        let (width, height) = target.get_dimensions();
+        let uniforms = glium::uniform! {
+            resolution: [width as f32, height as f32],
+            font: &self.font,
+        };
+
+        target
+            .draw(
+                &vertex_buffer,
+                glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                &self.program,
+                &uniforms,
+                &glium::DrawParameters {
+                    blend: glium::Blend::alpha_blending(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+    }
+}
+
+// size of lines of text, in pixels
+fn text_size(lines: &[String]) -> [f32; 2] {
+    [
+        lines.iter().map(|x| x.len()).max().unwrap_or(0) as f32 * GLYPH_WIDTH as f32 * TEXT_SCALE,
+        lines.len() as f32 * GLYPH_HEIGHT as f32 * TEXT_SCALE,
+    ]
+}
+
+// add lines of text, from the top left
+fn text_lines(vertices: &mut Vec<OverlayVert>, pos: [f32; 2], lines: &[String]) {
+    for (i, line) in lines.iter().enumerate() {
+        text(
+            vertices,
+            [pos[0], pos[1] + i as f32 * GLYPH_HEIGHT as f32 * TEXT_SCALE],
+            line,
+            [1.0, 1.0, 1.0, 1.0],
+        );
+    }
+}
+
+// add a rectangle, made of two triangles
+fn rectangle(vertices: &mut Vec<OverlayVert>, pos: [f32; 2], size: [f32; 2], color: [f32; 4]) {
+    textured_rectangle(vertices, pos, size, [-1.0, -1.0], [0.0, 0.0], color);
+}
+
+// add a rectangle with part of the font texture on it
+fn textured_rectangle(
+    vertices: &mut Vec<OverlayVert>,
+    pos: [f32; 2],
+    size: [f32; 2],
+    uv: [f32; 2],
+    uv_size: [f32; 2],
+    color: [f32; 4],
+) {
+    let corner = |x: f32, y: f32| OverlayVert {
+        pos: [pos[0] + size[0] * x, pos[1] + size[1] * y],
+        uv: [uv[0] + uv_size[0] * x, uv[1] + uv_size[1] * y],
+        color,
+    };
+    vertices.extend([
+        corner(0.0, 0.0),
+        corner(1.0, 0.0),
+        corner(0.0, 1.0),
+        corner(1.0, 0.0),
+        corner(1.0, 1.0),
+        corner(0.0, 1.0),
+    ]);
+}
+
+// add a line of text, characters outside of ascii are shown as a question mark
+fn text(vertices: &mut Vec<OverlayVert>, pos: [f32; 2], text: &str, color: [f32; 4]) {
+    for (i, c) in text.chars().enumerate() {
+        let index = match c {
+            ' '..='~' => c as u32 - ' ' as u32,
+            _ => '?' as u32 - ' ' as u32,
+        };
+        let glyph = [
+            (index % (FONT_WIDTH / GLYPH_WIDTH) * GLYPH_WIDTH) as f32,
+            (index / (FONT_WIDTH / GLYPH_WIDTH) * GLYPH_HEIGHT) as f32,
+        ];
+        textured_rectangle(
+            vertices,
+            [
+                pos[0] + (i as u32 * GLYPH_WIDTH) as f32 * TEXT_SCALE,
+                pos[1],
+            ],
+            [
+                GLYPH_WIDTH as f32 * TEXT_SCALE,
+                GLYPH_HEIGHT as f32 * TEXT_SCALE,
+            ],
+            glyph,
+            [GLYPH_WIDTH as f32, GLYPH_HEIGHT as f32],
+            color,
+        );
+    }
+}
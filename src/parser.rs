@@ -1,23 +1,58 @@
+use crate::args::{FrameRate, MessageFormat};
+use crate::buffer::{Buffer, Channel, Format, Preserve, Size, Storage, MAX_ATTACHMENTS};
+use crate::cache::Cache;
+use crate::compiler::all_compiled;
+use crate::diagnostic::{message, Diagnostic, Severity};
 use crate::drawer::*;
-use crate::program::load_program;
+use crate::graph::execution_order;
+use crate::preprocess::{canonical, file_name, Preprocessor};
+use crate::program::{compile, ProgramSource, Version};
+use glium::glutin::event::VirtualKeyCode;
 use image::Rgba32FImage;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use toml::Value;
 
-// TODO: nicer error reporting
-
 /// what to give to the shader input
+#[derive(Default, PartialEq)]
 pub enum ShaderInput {
     Texture(Rgba32FImage),
-    Buffer(usize),
+    // name of the pass, and which of its attachments
+    Buffer(String, usize),
     Keyboard,
+    #[default]
     None,
 }
 
-impl Default for ShaderInput {
-    fn default() -> Self {
-        Self::None
-    }
+/// a single pass, as set in the config
+#[derive(Default)]
+pub struct Pass {
+    // name of the pass, the key of its table in the config
+    pub name: String,
+
+    // source of the shader, with includes expanded
+    pub shader: String,
+
+    // file the shader is in
+    pub path: PathBuf,
+
+    // inputs for the shader
+    pub inputs: [ShaderInput; 4],
+
+    // how big the buffer is, what it stores, and how many outputs it has
+    pub storage: Storage,
+}
+
+/// a define that's turned on and off with a key
+pub struct Toggle {
+    // name of the define
+    pub name: String,
+
+    // key that turns it on and off
+    pub key: VirtualKeyCode,
+
+    // whether it's defined
+    pub on: bool,
 }
 
 // full information for a parsed shader
@@ -26,164 +61,933 @@ pub struct Shadertoy {
     // file to use
     config_file: PathBuf,
 
+    // directories to look for includes in, from the command line
+    include_path: Vec<PathBuf>,
+
+    // defines and glsl version from the command line
+    cli_defines: Vec<(String, String)>,
+    cli_version: Option<Version>,
+
+    // how to print compile errors
+    message_format: MessageFormat,
+
+    // where to keep compiled programs, if anywhere
+    cache: Option<Cache>,
+
     // files to watch, if these change, a reparse might be needed
     files_to_watch: Vec<PathBuf>,
 
-    // main shader
-    main_shader: String,
+    // when any of the watched files was last modified, when parsed
+    modified: Option<SystemTime>,
+
+    // whether the config couldn't be parsed, and this is an empty shader instead
+    failed: bool,
+
+    // what's wrong with the files, found when parsing
+    errors: Vec<Diagnostic>,
+
+    // directory the config is in, file names are shown relative to it
+    dir: PathBuf,
 
-    // buffer shaders
-    ichannel_shaders: [String; 4],
+    // all passes, main is always first
+    passes: Vec<Pass>,
 
-    // common shader
+    // common shader, with includes expanded, and the file it's in
     common: String,
+    common_path: Option<PathBuf>,
+
+    // included files, by source number from FIRST_INCLUDE
+    includes: Vec<PathBuf>,
+
+    // defines for every pass, as name and value, from the config and command line
+    defines: Vec<(String, String)>,
+
+    // defines that are turned on and off with a key
+    toggles: Vec<Toggle>,
+
+    // how many samples to accumulate for the main shader, if any
+    accumulate: Option<u32>,
+
+    // how often to draw frames, if set
+    fps: Option<FrameRate>,
 
-    // inputs for shaders
-    main_inputs: [ShaderInput; 4],
+    // whether to wait for vsync, if set
+    vsync: Option<bool>,
 
-    // inputs for the channels
-    ichannel_inputs: [[ShaderInput; 4]; 4],
+    // glsl version to compile as, if set
+    version: Option<Version>,
 }
 
 impl Shadertoy {
-    // parses from a toml value
-    fn from_toml(path: &Path, value: Value) -> Option<Self> {
+    // parses from a toml value, with the preprocessor for its search path
+    // what's wrong with it is added to the errors, even if it can't be parsed
+    fn from_toml(
+        path: &Path,
+        value: Value,
+        preprocessor: &mut Preprocessor,
+        errors: &mut Vec<Diagnostic>,
+    ) -> Option<Self> {
+        // paths in the config are relative to it
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let config = file_name(path, &canonical(dir));
+        let error = |message| Diagnostic::in_file(config.clone(), None, Severity::Error, message);
+        let warning =
+            |message| Diagnostic::in_file(config.clone(), None, Severity::Warning, message);
+
         // parse our config
-        let mut conf = Shadertoy::default();
+        let mut conf = Shadertoy {
+            // set the file to watch
+            config_file: path.into(),
+            files_to_watch: vec![path.into()],
+            dir: dir.into(),
+            ..Default::default()
+        };
 
-        // set the file to watch
-        conf.config_file = path.into();
+        // main is required, and goes first
+        let Some(main) = value.get("main") else {
+            errors.push(error(
+                "there is no [main] table, with the shader to show".into(),
+            ));
+            return None;
+        };
+        conf.passes.push(Self::toml_pass(
+            "main",
+            main,
+            dir,
+            preprocessor,
+            &config,
+            errors,
+        )?);
 
-        // TODO: modify these
-        conf.files_to_watch = vec![path.into()];
+        // any other table with a shader is a pass too
+        for (name, block) in value.as_table()? {
+            if !["main", "defines", "toggles"].contains(&name.as_str())
+                && block.get("shader").is_some()
+            {
+                conf.passes.push(Self::toml_pass(
+                    name,
+                    block,
+                    dir,
+                    preprocessor,
+                    &config,
+                    errors,
+                )?);
+            }
+        }
 
-        // get the blocks
-        let main_shader = Self::toml_block(value.get("main")?, path)?;
-        let channel_0 = Self::toml_block(value.get("ichannel0")?, path)?;
-        let channel_1 = Self::toml_block(value.get("ichannel1")?, path)?;
-        let channel_2 = Self::toml_block(value.get("ichannel2")?, path)?;
-        let channel_3 = Self::toml_block(value.get("ichannel3")?, path)?;
+        // passes reading passes that don't exist, or attachments they don't have, get nothing
+        for pass in &conf.passes {
+            for input in &pass.inputs {
+                let ShaderInput::Buffer(name, attachment) = input else {
+                    continue;
+                };
+                match conf.passes.iter().find(|x| &x.name == name) {
+                    None => errors.push(warning(format!(
+                        "pass {} reads from {}, which is not a pass or image",
+                        pass.name, name
+                    ))),
+                    Some(x) if *attachment >= x.storage.attachments => {
+                        errors.push(warning(format!(
+                            "pass {} reads attachment {} of {}, which only has {}",
+                            pass.name, attachment, name, x.storage.attachments
+                        )))
+                    }
+                    Some(_) => (),
+                }
+            }
+        }
 
-        // special case
-        conf.common = std::fs::read_to_string(value.get("common")?.as_str()?).ok()?;
+        // optional, only for the main shader
+        conf.accumulate = match main.get("accumulate") {
+            Some(x) => match x.as_integer().and_then(|x| u32::try_from(x).ok()) {
+                Some(samples) if samples > 0 => Some(samples),
+                _ => {
+                    let message = format!("accumulate should be a number of at least 1, not {}", x);
+                    errors.push(error(message));
+                    return None;
+                }
+            },
+            None => None,
+        };
 
-        // and plug them into the conf
-        conf.main_shader = main_shader.0;
-        conf.main_inputs = main_shader.1;
+        // optional, how to pace frames, wrong values are left unset
+        conf.fps = match value.get("fps") {
+            Some(x) => match x
+                .as_str()
+                .map_or_else(|| x.to_string(), str::to_string)
+                .parse()
+            {
+                Ok(fps) => Some(fps),
+                Err(_) => {
+                    let message =
+                        format!("fps should be a positive number or \"uncapped\", not {}", x);
+                    errors.push(warning(message));
+                    None
+                }
+            },
+            None => None,
+        };
+        conf.vsync = match value.get("vsync") {
+            Some(Value::Boolean(x)) => Some(*x),
+            Some(x) => {
+                errors.push(warning(format!("vsync should be true or false, not {}", x)));
+                None
+            }
+            None => None,
+        };
 
-        // and ichannels
-        conf.ichannel_shaders = [channel_0.0, channel_1.0, channel_2.0, channel_3.0];
-        conf.ichannel_inputs = [channel_0.1, channel_1.1, channel_2.1, channel_3.1];
+        // optional, what to compile the shaders as
+        conf.version = match value.get("version") {
+            Some(Value::String(x)) => x.parse().ok(),
+            Some(x) => x.to_string().parse().ok(),
+            None => None,
+        };
+        if let (Some(x), None) = (value.get("version"), conf.version) {
+            let message = format!("version should be 140, 330, 450 or \"es300\", not {}", x);
+            errors.push(warning(message));
+        }
+
+        // optional, defines for every pass
+        if let Some(defines) = value.get("defines") {
+            for (name, value) in defines.as_table()? {
+                match value {
+                    // false leaves it undefined
+                    Value::Boolean(false) => (),
+                    Value::Boolean(true) => conf.defines.push((name.clone(), String::new())),
+                    Value::String(x) => conf.defines.push((name.clone(), x.clone())),
+                    Value::Integer(_) | Value::Float(_) => {
+                        conf.defines.push((name.clone(), value.to_string()))
+                    }
+                    _ => {
+                        let message =
+                            format!("define {} should be a string, number or boolean", name);
+                        errors.push(error(message));
+                        return None;
+                    }
+                }
+            }
+        }
+
+        // optional, defines turned on and off with a key
+        if let Some(toggles) = value.get("toggles") {
+            for (name, toggle) in toggles.as_table()? {
+                let key = toggle.get("key").and_then(Value::as_str);
+                let on = toggle.get("on").map(Value::as_bool);
+                match (key.and_then(toggle_key), on) {
+                    (Some(key), None | Some(Some(_))) => conf.toggles.push(Toggle {
+                        name: name.clone(),
+                        key,
+                        on: on.flatten().unwrap_or(false),
+                    }),
+                    (None, _) => {
+                        errors.push(error(format!(
+                            "key of toggle {} should be a letter other than I and R, or F5 to F12",
+                            name
+                        )));
+                        return None;
+                    }
+                    (_, Some(None)) => {
+                        let message = format!("on of toggle {} should be true or false", name);
+                        errors.push(error(message));
+                        return None;
+                    }
+                }
+            }
+        }
+
+        // optional, shared by all passes
+        if let Some(common) = value.get("common") {
+            let common = dir.join(common.as_str()?);
+            let source = preprocessor.read(&common)?;
+            conf.common = preprocessor.expand(&source, &common, 1)?;
+            conf.common_path = Some(common);
+        }
+
+        // watch every file the passes are made from
+        conf.files_to_watch
+            .extend(conf.passes.iter().map(|x| x.path.clone()));
+        conf.files_to_watch.extend(conf.common_path.clone());
+        conf.files_to_watch.extend_from_slice(&preprocessor.files);
+        conf.includes = preprocessor.files.clone();
 
         // and return it
         Some(conf)
     }
 
     // parses an ichannel (ichannel0 = ...) from a value
-    fn toml_ichannel(value: &Value, channel: usize, path: &Path) -> Option<ShaderInput> {
+    fn toml_ichannel(
+        value: &Value,
+        channel: usize,
+        dir: &Path,
+        config: &str,
+        errors: &mut Vec<Diagnostic>,
+    ) -> Option<ShaderInput> {
         // try to get the right texture
         match value.get(format!("ichannel{}", channel)) {
-            // just a buffer
-            Some(Value::Integer(x)) => Some(ShaderInput::Buffer(*x as usize)),
-
-            // read the actual texture file
-            Some(Value::String(string)) => Some(ShaderInput::Texture(
-                match image::io::Reader::open(path.join(&string)) {
-                    Ok(x) => Some(x),
-                    Err(x) => {
-                        println!("Failed to load image {:?}: {:?}", path.join(&string), x);
-                        None
+            // buffers used to be numbered, these are the ichannel passes
+            Some(Value::Integer(x)) => Some(ShaderInput::Buffer(format!("ichannel{}", x), 0)),
+
+            // an image file if it has the extension of one, otherwise a pass
+            // an image that can't be loaded leaves the channel empty
+            Some(Value::String(string)) if image::ImageFormat::from_path(string).is_ok() => {
+                let path = dir.join(string);
+                let image = image::io::Reader::open(&path)
+                    .map_err(|x| x.to_string())
+                    .and_then(|x| x.decode().map_err(|x| x.to_string()));
+                match image {
+                    Ok(x) => Some(ShaderInput::Texture(x.into_rgba32f())),
+                    Err(reason) => {
+                        let message = format!("failed to load image {}: {}", string, reason);
+                        let severity = Severity::Warning;
+                        errors.push(Diagnostic::in_file(config.into(), None, severity, message));
+                        Some(ShaderInput::None)
                     }
-                }?
-                .decode()
-                .ok()?
-                .into_rgba32f(),
-            )),
+                }
+            }
+            Some(Value::String(string)) => Some(Self::buffer_input(string)),
             _ => Some(ShaderInput::None),
         }
     }
 
-    // parses a block (entire shader definition) from a toml value
-    fn toml_block(value: &Value, path: &Path) -> Option<(String, [ShaderInput; 4])> {
+    // a pass as input, as name.attachment, or just the name for the first attachment
+    fn buffer_input(string: &str) -> ShaderInput {
+        match string.rsplit_once('.').map(|(name, x)| (name, x.parse())) {
+            Some((name, Ok(attachment))) => ShaderInput::Buffer(name.to_string(), attachment),
+            _ => ShaderInput::Buffer(string.to_string(), 0),
+        }
+    }
+
+    // parses a pass (entire shader definition) from a toml value
+    fn toml_pass(
+        name: &str,
+        value: &Value,
+        dir: &Path,
+        preprocessor: &mut Preprocessor,
+        config: &str,
+        errors: &mut Vec<Diagnostic>,
+    ) -> Option<Pass> {
         // get the shader
-        let shader = match std::fs::read_to_string(path.join(value.get("shader")?.as_str()?)) {
-            Ok(x) => Some(x),
-            Err(x) => {
-                println!(
-                    "Failed to load shader {:?}: {:?}",
-                    path.join(value.get("shader")?.as_str()?),
-                    x
-                );
-                None
-            }
-        }?;
+        let path = dir.join(value.get("shader")?.as_str()?);
+        let source = preprocessor.read(&path)?;
+        let shader = preprocessor.expand(&source, &path, 2)?;
+
         // and inputs
-        let inp_0 = Self::toml_ichannel(&value, 0, path)?;
-        let inp_1 = Self::toml_ichannel(&value, 1, path)?;
-        let inp_2 = Self::toml_ichannel(&value, 2, path)?;
-        let inp_3 = Self::toml_ichannel(&value, 3, path)?;
+        let inp_0 = Self::toml_ichannel(value, 0, dir, config, errors)?;
+        let inp_1 = Self::toml_ichannel(value, 1, dir, config, errors)?;
+        let inp_2 = Self::toml_ichannel(value, 2, dir, config, errors)?;
+        let inp_3 = Self::toml_ichannel(value, 3, dir, config, errors)?;
+
+        // anything else that's wrong means the pass can't be made
+        let mut error = |message| {
+            errors.push(Diagnostic::in_file(
+                config.into(),
+                None,
+                Severity::Error,
+                message,
+            ));
+            None
+        };
+
+        // optional, either a fixed size or a scale relative to the window
+        let size = match (value.get("size"), value.get("scale")) {
+            (Some(_), Some(_)) => {
+                return error(format!("pass {} can't have both a size and a scale", name));
+            }
+            (Some(size), None) => match size.as_array().map(Vec::as_slice) {
+                Some([Value::Integer(w), Value::Integer(h)]) if *w > 0 && *h > 0 => {
+                    Size::Fixed(*w as u32, *h as u32)
+                }
+                _ => return error(format!("size of pass {} should be [width, height]", name)),
+            },
+            (None, Some(scale)) => match scale {
+                Value::Float(x) if *x > 0.0 => Size::Scaled(*x as f32),
+                Value::Integer(x) if *x > 0 => Size::Scaled(*x as f32),
+                _ => {
+                    return error(format!(
+                        "scale of pass {} should be a positive number",
+                        name
+                    ))
+                }
+            },
+            (None, None) => Size::default(),
+        };
+
+        // optional, what to store the pixels as
+        let format = match value.get("format") {
+            Some(format) => match format.as_str().map(str::parse) {
+                Some(Ok(x)) => x,
+                _ => {
+                    return error(format!(
+                        "format of pass {} should be rgba8, rgba16f or rgba32f",
+                        name
+                    ))
+                }
+            },
+            None => Format::default(),
+        };
+
+        // optional, how to keep the contents when the window is resized
+        let preserve = match value.get("preserve") {
+            Some(preserve) => match preserve.as_str().map(str::parse) {
+                Some(Ok(x)) => x,
+                _ => {
+                    return error(format!(
+                        "preserve of pass {} should be scale, copy or clear",
+                        name
+                    ))
+                }
+            },
+            None => Preserve::default(),
+        };
+
+        // optional, how many outputs the shader has
+        let attachments = match value.get("attachments") {
+            Some(Value::Integer(x)) if (1..=MAX_ATTACHMENTS as i64).contains(x) => *x as usize,
+            Some(_) => {
+                return error(format!(
+                    "attachments of pass {} should be between 1 and {}",
+                    name, MAX_ATTACHMENTS
+                ))
+            }
+            None => 1,
+        };
 
         // and generate it
-        Some((shader, [inp_0, inp_1, inp_2, inp_3]))
+        Some(Pass {
+            name: name.to_string(),
+            shader,
+            path,
+            inputs: [inp_0, inp_1, inp_2, inp_3],
+            storage: Storage {
+                size,
+                format,
+                preserve,
+                attachments,
+            },
+        })
+    }
+
+    /// parse a config file from a given path, includes are also looked for in the include path
+    /// the defines are added to the ones in the config, and replace them if they have the same name
+    /// the version replaces the one in the config, if set
+    pub fn new(
+        path: &Path,
+        include_path: &[PathBuf],
+        defines: &[(String, String)],
+        version: Option<Version>,
+        message_format: MessageFormat,
+        cache: Option<Cache>,
+    ) -> Self {
+        let mut conf = Self::parse(path, include_path);
+        conf.message_format = message_format;
+        conf.cache = cache;
+        conf.include_path = include_path.to_vec();
+        conf.cli_defines = defines.to_vec();
+        conf.cli_version = version;
+        conf.version = version.or(conf.version);
+        conf.defines
+            .retain(|(name, _)| !defines.iter().any(|x| &x.0 == name));
+        conf.defines.extend_from_slice(defines);
+        conf.modified = last_modified(&conf.files_to_watch);
+        conf
     }
 
-    /// parse a config file from a given path
-    pub fn new(path: &Path) -> Self {
+    // parse a config file, or a shader if it's not one
+    fn parse(path: &Path, include_path: &[PathBuf]) -> Self {
         // load the contents
-        let contents = match std::fs::read_to_string(path) {
-            Ok(x) => x,
-            Err(reason) => {
-                println!("Failed to load shader {:?}: {:?}", path, reason);
-                return Self {
-                    files_to_watch: vec![path.into()],
-                    config_file: path.into(),
-                    ..Default::default()
-                };
-            }
+        let mut preprocessor =
+            Preprocessor::new(vec![path.parent().unwrap_or(Path::new("")).into()]);
+        let contents = match preprocessor.read(path) {
+            Some(x) => x,
+            None => return Self::failed(path, Vec::new(), include_path, preprocessor.errors),
         };
 
         // try and parse into a toml file
         match toml::from_str(&contents) {
-            Ok(config) => match Self::from_toml(path, config) {
-                Some(x) => x,
-                _ => Self {
-                    files_to_watch: vec![path.into()],
-                    config_file: path.into(),
-                    ..Default::default()
-                },
-            },
+            Ok(config) => {
+                let files = config_files(path, &config);
+                let mut preprocessor = Preprocessor::new(search_path(path, &config, include_path));
+                let mut errors = Vec::new();
+                let conf = Self::from_toml(path, config, &mut preprocessor, &mut errors);
+                errors.append(&mut preprocessor.errors);
+                match conf {
+                    Some(mut x) => {
+                        x.errors = errors;
+                        x
+                    }
+                    _ => Self::failed(path, files, include_path, errors),
+                }
+            }
             Err(reason) => {
-                // say the parse error reason
-                println!("Failed to parse as toml: {}", reason);
-                println!("Interpreting as shader instead");
-
-                // return our
-                Self {
-                    files_to_watch: vec![path.into()],
-                    config_file: path.into(),
-                    main_shader: contents,
-                    ..Default::default()
+                // say why, at where it stopped being toml
+                let mut conf = Self::single(path, contents, include_path);
+                let message = format!(
+                    "failed to parse as toml, interpreting as shader instead: {}",
+                    reason
+                );
+                let mut warning = Diagnostic::in_file(
+                    file_name(path, &conf.dir()),
+                    None,
+                    Severity::Warning,
+                    message,
+                );
+                warning.line = reason.line_col().map(|(line, _)| line + 1);
+                warning.column = reason.line_col().map(|(_, column)| column + 1);
+                conf.errors.insert(0, warning);
+                conf
+            }
+        }
+    }
+
+    // an empty shader, for a config that couldn't be parsed, with what was wrong with it
+    // the given files are watched as well, so fixing them reloads it
+    fn failed(
+        path: &Path,
+        files: Vec<PathBuf>,
+        include_path: &[PathBuf],
+        errors: Vec<Diagnostic>,
+    ) -> Self {
+        let mut conf = Self::single(path, String::new(), include_path);
+        conf.files_to_watch.extend(files);
+        conf.failed = true;
+        conf.errors = errors;
+
+        // not everything that's wrong says what
+        if !conf.errors.iter().any(|x| x.severity == Severity::Error) {
+            let message = "failed to load, a value is missing or has the wrong type".to_string();
+            let file = file_name(path, &conf.dir());
+            conf.errors
+                .push(Diagnostic::in_file(file, None, Severity::Error, message));
+        }
+        conf
+    }
+
+    // a single shader file, with only a main pass
+    fn single(path: &Path, shader: String, include_path: &[PathBuf]) -> Self {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut search_path = vec![dir.to_path_buf()];
+        search_path.extend_from_slice(include_path);
+        let mut preprocessor = Preprocessor::new(search_path);
+        let shader = preprocessor.expand(&shader, path, 2);
+
+        Self {
+            files_to_watch: std::iter::once(path.to_path_buf())
+                .chain(preprocessor.files.iter().cloned())
+                .collect(),
+            config_file: path.into(),
+            dir: dir.into(),
+            includes: preprocessor.files,
+            failed: shader.is_none(),
+            errors: preprocessor.errors,
+            passes: vec![Pass {
+                name: "main".to_string(),
+                shader: shader.unwrap_or_default(),
+                path: path.into(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    /// how many samples to accumulate for the main shader, if set in the config
+    pub fn accumulate(&self) -> Option<u32> {
+        self.accumulate
+    }
+
+    /// how often to draw frames, if set in the config
+    pub fn fps(&self) -> Option<FrameRate> {
+        self.fps
+    }
+
+    /// whether to wait for vsync, if set in the config
+    pub fn vsync(&self) -> Option<bool> {
+        self.vsync
+    }
+
+    /// what's wrong with the files, which is why the project is empty if it failed to load
+    pub fn errors(&self) -> &[Diagnostic] {
+        &self.errors
+    }
+
+    /// print what's wrong with the files, in the message format
+    pub fn print_errors(&self) {
+        for error in &self.errors {
+            error.print(self.message_format);
+        }
+    }
+
+    /// whether the project couldn't be loaded, and has an empty pass instead
+    pub fn failed_to_load(&self) -> bool {
+        self.failed
+    }
+
+    /// directory the config is in, file names in errors are relative to it
+    pub fn dir(&self) -> PathBuf {
+        canonical(&self.dir)
+    }
+
+    /// every shader file used, the passes, common, and the included files
+    pub fn shader_files(&self) -> Vec<PathBuf> {
+        self.passes
+            .iter()
+            .map(|x| x.path.clone())
+            .chain(self.common_path.clone())
+            .chain(self.includes.iter().cloned())
+            .collect()
+    }
+
+    /// whether any of the files the config is made from changed since it was parsed
+    pub fn modified(&self) -> bool {
+        last_modified(&self.files_to_watch) != self.modified
+    }
+
+    /// parse the config again, to pick up changes to any of its files
+    /// toggles stay on or off
+    /// gives the sources of the passes whose program changed, with their index,
+    /// or None if the passes themselves changed, and all of them have to be set again
+    pub fn reload(&mut self) -> Option<Vec<(usize, ProgramSource)>> {
+        let old = std::mem::replace(
+            self,
+            Self::new(
+                &self.config_file,
+                &self.include_path,
+                &self.cli_defines,
+                self.cli_version,
+                self.message_format,
+                self.cache.clone(),
+            ),
+        );
+        for toggle in &mut self.toggles {
+            if let Some(old) = old.toggles.iter().find(|x| x.name == toggle.name) {
+                toggle.on = old.on;
+            }
+        }
+
+        // a config that couldn't be parsed doesn't know all its files, such as includes
+        // so the old ones are still watched, to reload when they're fixed
+        if self.failed {
+            for file in &old.files_to_watch {
+                if !self.files_to_watch.contains(file) {
+                    self.files_to_watch.push(file.clone());
                 }
             }
+            self.modified = last_modified(&self.files_to_watch);
+        }
+
+        // passes that are added, removed, or read or store something else need new buffers
+        let same = self.passes.len() == old.passes.len()
+            && self.passes.iter().zip(&old.passes).all(|(new, old)| {
+                new.name == old.name && new.inputs == old.inputs && new.storage == old.storage
+            });
+        if !same {
+            return None;
+        }
+
+        // a change to common, an include or a define shows up in the source of every pass using it
+        Some(
+            self.program_sources()
+                .into_iter()
+                .zip(old.program_sources())
+                .filter(|((_, new), (_, old))| {
+                    new.vertex != old.vertex || new.fragment != old.fragment
+                })
+                .map(|(new, _)| new)
+                .collect(),
+        )
+    }
+
+    /// turn the define bound to the key on or off, if there is one, and give its name
+    pub fn toggle(&mut self, key: VirtualKeyCode) -> Option<String> {
+        let toggle = self.toggles.iter_mut().find(|x| x.key == key)?;
+        toggle.on = !toggle.on;
+        let text = format!(
+            "{} is {}",
+            toggle.name,
+            if toggle.on { "on" } else { "off" }
+        );
+        message(self.message_format, &text);
+        Some(toggle.name.clone())
+    }
+
+    /// compile some passes again on this thread, they keep what they drew
+    /// none are swapped in if one fails, which gives the index of every pass instead
+    pub fn recompile(
+        &self,
+        display: &glium::Display,
+        drawer: &mut Drawer,
+        sources: Vec<(usize, ProgramSource)>,
+    ) -> Result<(), Vec<usize>> {
+        let passes: Vec<usize> = sources.iter().map(|x| x.0).collect();
+        let programs = all_compiled(self.compile(display, sources)).ok_or(passes)?;
+        self.swap_programs(drawer, programs);
+        Ok(())
+    }
+
+    /// swap in new programs for some passes, with their index
+    pub fn swap_programs(&self, drawer: &mut Drawer, programs: Vec<(usize, glium::Program)>) {
+        for (i, program) in programs {
+            drawer.buffers[i].program = Some(program);
+            drawer.buffers[i].sources = self.source_names(i);
         }
     }
-    /// optionally reloads the shader
-    pub fn reload(&mut self) -> bool {
-        false
+
+    /// defines for every pass, with the toggles that are on
+    pub fn defines(&self) -> Vec<(String, String)> {
+        let toggles = self
+            .toggles
+            .iter()
+            .filter(|x| x.on && !self.defines.iter().any(|(name, _)| name == &x.name))
+            .map(|x| (x.name.clone(), String::new()));
+        self.defines.iter().cloned().chain(toggles).collect()
+    }
+
+    // source of the program of a pass
+    fn program_source(&self, pass: &Pass) -> ProgramSource {
+        ProgramSource::new(
+            &pass.name,
+            self.pass_names(pass),
+            &self.common,
+            &pass.shader,
+            pass.storage.attachments,
+            &self.defines(),
+            self.version.unwrap_or_default(),
+        )
+    }
+
+    /// sources of the programs of every pass, with the index of the pass
+    pub fn program_sources(&self) -> Vec<(usize, ProgramSource)> {
+        self.passes
+            .iter()
+            .map(|pass| self.program_source(pass))
+            .enumerate()
+            .collect()
+    }
+
+    /// sources of the programs of the passes using a define, which need recompiling when it's toggled
+    pub fn toggled_sources(&self, define: &str) -> Vec<(usize, ProgramSource)> {
+        let common = self.common.contains(define);
+        self.passes
+            .iter()
+            .enumerate()
+            .filter(|(_, pass)| common || pass.shader.contains(define))
+            .map(|(i, pass)| (i, self.program_source(pass)))
+            .collect()
+    }
+
+    /// compile programs on this thread, and print why if any fail
+    pub fn compile(
+        &self,
+        display: &glium::Display,
+        sources: Vec<(usize, ProgramSource)>,
+    ) -> Vec<(usize, Option<glium::Program>)> {
+        sources
+            .into_iter()
+            .map(|(i, source)| {
+                let program = compile(display, &source, self.message_format, self.cache.as_ref());
+                (i, program)
+            })
+            .collect()
+    }
+
+    /// names of the files for every source number of the pass with the index, for debug prints
+    /// these change when includes are added, so they're set again with its program
+    pub fn source_names(&self, pass: usize) -> Vec<String> {
+        self.pass_names(&self.passes[pass])
+    }
+
+    // names of the files for every source number of a pass, as shown in errors and prints
+    fn pass_names(&self, pass: &Pass) -> Vec<String> {
+        let dir = self.dir();
+        let name = |path: &Path| file_name(path, &dir);
+
+        // 0 is never used, includes start after the pass
+        let mut names = vec![
+            String::new(),
+            self.common_path.as_deref().map(name).unwrap_or_default(),
+            name(&pass.path),
+        ];
+        names.extend(self.includes.iter().map(|x| name(x)));
+        names
     }
 
-    /// apply this to a drawer
+    /// apply this to a drawer, compiling every pass on this thread
     pub fn load_shaders(&self, display: &glium::Display, drawer: &mut Drawer) {
-        // load the main shader
-        drawer.buffers[0].program = load_program(display, &self.common, &self.main_shader);
+        let programs = self.compile(display, self.program_sources());
+        self.set_programs(display, drawer, programs.into_iter().map(|x| x.1).collect());
+    }
+
+    /// like load_shaders, but only if every pass compiled, and gives whether they did
+    /// the old passes keep running otherwise
+    pub fn reload_shaders(&self, display: &glium::Display, drawer: &mut Drawer) -> bool {
+        match all_compiled(self.compile(display, self.program_sources())) {
+            Some(programs) => {
+                let programs = programs.into_iter().map(|x| Some(x.1)).collect();
+                self.set_programs(display, drawer, programs);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// apply this to a drawer, with the compiled program of every pass
+    pub fn set_programs(
+        &self,
+        display: &glium::Display,
+        drawer: &mut Drawer,
+        programs: Vec<Option<glium::Program>>,
+    ) {
+        // find what passes read from, passes reading passes that don't exist get nothing
+        let inputs = self
+            .passes
+            .iter()
+            .map(|pass| {
+                pass.inputs
+                    .iter()
+                    .filter_map(|input| match input {
+                        ShaderInput::Buffer(name, _) => {
+                            self.passes.iter().position(|x| &x.name == name)
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<usize>>()
+            })
+            .collect::<Vec<Vec<usize>>>();
+
+        // and run them in the right order
+        let (order, mut previous) = execution_order(&inputs, 0);
+        previous.sort_unstable();
+        previous.dedup();
+        for (reader, pass) in previous {
+            let text = format!(
+                "Pass {} reads {} from the previous frame",
+                self.passes[reader].name, self.passes[pass].name
+            );
+            message(self.message_format, &text);
+        }
+
+        // make the buffers
+        let buffers = self
+            .passes
+            .iter()
+            .zip(programs)
+            .map(|(pass, program)| {
+                let channels = pass.inputs.each_ref().map(|input| match input {
+                    ShaderInput::Texture(image) => Channel::Texture(
+                        glium::Texture2d::new(
+                            display,
+                            glium::texture::RawImage2d::from_raw_rgba_reversed(
+                                image.as_raw(),
+                                image.dimensions(),
+                            ),
+                        )
+                        .expect("failed to make texture"),
+                    ),
+                    ShaderInput::Buffer(name, attachment) => {
+                        match self.passes.iter().position(|x| &x.name == name) {
+                            Some(i) if *attachment < self.passes[i].storage.attachments => {
+                                Channel::Buffer(i, *attachment)
+                            }
+                            _ => Channel::None,
+                        }
+                    }
+                    ShaderInput::Keyboard => Channel::Keyboard,
+                    ShaderInput::None => Channel::None,
+                });
 
-        // and the ichannel ones
-        drawer.buffers[1].program = load_program(display, &self.common, &self.ichannel_shaders[0]);
-        drawer.buffers[2].program = load_program(display, &self.common, &self.ichannel_shaders[1]);
-        drawer.buffers[3].program = load_program(display, &self.common, &self.ichannel_shaders[2]);
-        drawer.buffers[4].program = load_program(display, &self.common, &self.ichannel_shaders[3]);
+                Buffer::new(
+                    display,
+                    &pass.name,
+                    pass.storage,
+                    program,
+                    channels,
+                    self.pass_names(pass),
+                )
+            })
+            .collect();
 
-        // and the inputs
-        // drawer.buffers[0].channels
+        drawer.set_passes(display, buffers, order);
     }
 }
+
+// key a toggle can be bound to, by name
+// only letters and function keys that aren't used for anything else
+fn toggle_key(name: &str) -> Option<VirtualKeyCode> {
+    use VirtualKeyCode::*;
+    let keys = [
+        ("A", A),
+        ("B", B),
+        ("C", C),
+        ("D", D),
+        ("E", E),
+        ("F", F),
+        ("G", G),
+        ("H", H),
+        ("J", J),
+        ("K", K),
+        ("L", L),
+        ("M", M),
+        ("N", N),
+        ("O", O),
+        ("P", P),
+        ("Q", Q),
+        ("S", S),
+        ("T", T),
+        ("U", U),
+        ("V", V),
+        ("W", W),
+        ("X", X),
+        ("Y", Y),
+        ("Z", Z),
+        ("F5", F5),
+        ("F6", F6),
+        ("F7", F7),
+        ("F8", F8),
+        ("F9", F9),
+        ("F10", F10),
+        ("F11", F11),
+        ("F12", F12),
+    ];
+    keys.iter()
+        .find(|x| x.0.eq_ignore_ascii_case(name))
+        .map(|x| x.1)
+}
+
+// shader files named in a config, to watch when it can't be parsed
+fn config_files(path: &Path, value: &Value) -> Vec<PathBuf> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let common = value.get("common").and_then(Value::as_str);
+    let shaders = value
+        .as_table()
+        .into_iter()
+        .flatten()
+        .filter_map(|(_, x)| x.get("shader")?.as_str());
+    common
+        .into_iter()
+        .chain(shaders)
+        .map(|x| dir.join(x))
+        .collect()
+}
+
+// where to look for includes, the project, then the config's include path, then the command line's
+fn search_path(path: &Path, value: &Value, include_path: &[PathBuf]) -> Vec<PathBuf> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let paths = value.get("include_path").and_then(Value::as_array);
+    std::iter::once(dir.to_path_buf())
+        .chain(
+            paths
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .map(|x| dir.join(x)),
+        )
+        .chain(include_path.iter().cloned())
+        .collect()
+}
+
+// latest modification time of the files, if any of them exist
+fn last_modified(files: &[PathBuf]) -> Option<SystemTime> {
+    files
+        .iter()
+        .filter_map(|x| std::fs::metadata(x).and_then(|x| x.modified()).ok())
+        .max()
+}
@@ -0,0 +1,364 @@
+use std::path::{Path, PathBuf};
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::library;
+
+/// source number of the first included file, 1 is common and 2 is the pass itself
+pub const FIRST_INCLUDE: usize = 3;
+
+/// expands #include directives, and keeps track of the files included
+pub struct Preprocessor {
+    // directories to look in, the project directory first
+    search_path: Vec<PathBuf>,
+
+    // every file included so far, the first has source number FIRST_INCLUDE
+    pub files: Vec<PathBuf>,
+
+    // files that couldn't be read, and includes that couldn't be expanded, at the #include
+    pub errors: Vec<Diagnostic>,
+}
+
+impl Preprocessor {
+    pub fn new(search_path: Vec<PathBuf>) -> Self {
+        Self {
+            search_path,
+            files: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// read a shader, or keep why it can't be read
+    pub fn read(&mut self, path: &Path) -> Option<String> {
+        match std::fs::read_to_string(path) {
+            Ok(x) => Some(x),
+            Err(reason) => {
+                self.error(path, None, format!("failed to read shader: {}", reason));
+                None
+            }
+        }
+    }
+
+    /// expand the includes in the source of a file, which has the given source number
+    /// returns None if an include can't be found, or includes itself
+    pub fn expand(&mut self, source: &str, path: &Path, number: usize) -> Option<String> {
+        let mut stack = vec![canonical(path)];
+        self.expand_file(source, path, number, &mut stack)
+    }
+
+    // expand the includes, with the files currently being expanded on the stack
+    fn expand_file(
+        &mut self,
+        source: &str,
+        path: &Path,
+        number: usize,
+        stack: &mut Vec<PathBuf>,
+    ) -> Option<String> {
+        let mut expanded = String::new();
+        for (line, text) in source.lines().enumerate() {
+            let (name, quoted) = match include(text) {
+                Some(x) => x,
+                None => {
+                    expanded.push_str(text);
+                    expanded.push('\n');
+                    continue;
+                }
+            };
+
+            // find it
+            let file = match self.find(name, quoted, path) {
+                Some(x) => x,
+                None => {
+                    self.error(path, Some(line + 1), format!("can't find include {}", name));
+                    return None;
+                }
+            };
+
+            // and make sure it's not already being expanded
+            if stack.contains(&file) {
+                let cycle = stack
+                    .iter()
+                    .chain(std::iter::once(&file))
+                    .map(|x| file_name(x, &self.dir()))
+                    .collect::<Vec<String>>();
+                let message = format!("include cycle: {}", cycle.join(" -> "));
+                self.error(path, Some(line + 1), message);
+                return None;
+            }
+
+            let contents = match library_file(&file) {
+                Some(x) => Ok(x.to_string()),
+                None => std::fs::read_to_string(&file),
+            };
+            let contents = match contents {
+                Ok(x) => x,
+                Err(reason) => {
+                    let message = format!("failed to read include {}: {}", name, reason);
+                    self.error(path, Some(line + 1), message);
+                    return None;
+                }
+            };
+
+            // the same file always has the same source number
+            let include_number = match self.files.iter().position(|x| x == &file) {
+                Some(i) => i + FIRST_INCLUDE,
+                None => {
+                    self.files.push(file.clone());
+                    self.files.len() - 1 + FIRST_INCLUDE
+                }
+            };
+
+            stack.push(file.clone());
+            let include_source = self.expand_file(&contents, &file, include_number, stack)?;
+            stack.pop();
+
+            // line numbers are reset, so they match the files, and continue after the include
+            expanded.push_str(&format!("#line 1 {}\n", include_number));
+            expanded.push_str(&include_source);
+            expanded.push_str(&format!("#line {} {}\n", line + 2, number));
+        }
+
+        Some(expanded)
+    }
+
+    // project directory, which is searched first
+    fn dir(&self) -> PathBuf {
+        self.search_path
+            .first()
+            .map(|x| canonical(x))
+            .unwrap_or_default()
+    }
+
+    // keep an error in a file, named relative to the project directory
+    fn error(&mut self, path: &Path, line: Option<usize>, message: String) {
+        let file = file_name(path, &self.dir());
+        self.errors
+            .push(Diagnostic::in_file(file, line, Severity::Error, message));
+    }
+
+    // where an include is, quoted includes look next to the including file first
+    // library files always come from the library, and have their name in <> as path
+    fn find(&self, name: &str, quoted: bool, path: &Path) -> Option<PathBuf> {
+        if !quoted && library::get(name).is_some() {
+            return Some(PathBuf::from(format!("<{}>", name)));
+        }
+
+        let here = path.parent().filter(|_| quoted).map(Path::to_path_buf);
+        here.iter()
+            .chain(&self.search_path)
+            .map(|dir| dir.join(name))
+            .find(|x| x.is_file())
+            .map(|x| canonical(&x))
+    }
+}
+
+/// name and whether it's quoted, if the line is an #include directive
+fn include(line: &str) -> Option<(&str, bool)> {
+    let name = line
+        .trim()
+        .strip_prefix('#')?
+        .trim_start()
+        .strip_prefix("include")?
+        .trim();
+
+    if let Some(name) = name.strip_prefix('"').and_then(|x| x.strip_suffix('"')) {
+        Some((name, true))
+    } else {
+        name.strip_prefix('<')
+            .and_then(|x| x.strip_suffix('>'))
+            .map(|x| (x, false))
+    }
+}
+
+/// source of a library file, if the path is one
+pub fn library_file(path: &Path) -> Option<&'static str> {
+    library::get(path.to_str()?.strip_prefix('<')?.strip_suffix('>')?)
+}
+
+/// the same path for the same file, so cycles are found no matter how it's included
+pub fn canonical(path: &Path) -> PathBuf {
+    // an empty path is the current directory
+    let path = if path.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        path
+    };
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// name of a file as shown in errors, relative to the directory if it's in it
+pub fn file_name(path: &Path, dir: &Path) -> String {
+    let path = canonical(path);
+    path.strip_prefix(dir)
+        .unwrap_or(&path)
+        .display()
+        .to_string()
+}
+
+/// replace source numbers in a compiler log with the names of the files they are
+/// names has the name for every source number
+pub fn name_sources(log: &str, names: &[String]) -> String {
+    log.lines()
+        .map(|line| {
+            // some drivers put the severity first
+            let start = ["ERROR: ", "WARNING: "]
+                .iter()
+                .find(|x| line.starts_with(*x))
+                .map_or(0, |x| x.len());
+
+            // source number, followed by the line
+            let digits = line[start..]
+                .chars()
+                .take_while(char::is_ascii_digit)
+                .count();
+            let rest = &line[start + digits..];
+            match line[start..start + digits].parse::<usize>() {
+                Ok(number)
+                    if number < names.len() && (rest.starts_with(':') || rest.starts_with('(')) =>
+                {
+                    format!("{}{}{}", &line[..start], names[number], rest)
+                }
+                _ => line.to_string(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a new directory with the given files, named for the test so tests don't share one
+    fn project(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("shadercrab-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for (name, contents) in files {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        canonical(&dir)
+    }
+
+    // expand a file of the project, as the pass with source number 2
+    fn expand(dir: &Path, name: &str) -> (Option<String>, Preprocessor) {
+        let mut preprocessor = Preprocessor::new(vec![dir.to_path_buf()]);
+        let path = dir.join(name);
+        let source = preprocessor.read(&path).unwrap();
+        (preprocessor.expand(&source, &path, 2), preprocessor)
+    }
+
+    #[test]
+    fn line_numbers() {
+        let dir = project(
+            "line-numbers",
+            &[
+                ("main.glsl", "a\n#include \"x.glsl\"\nb\n"),
+                ("x.glsl", "c\n#include \"y.glsl\"\nd\n"),
+                ("y.glsl", "e\n"),
+            ],
+        );
+        let (expanded, preprocessor) = expand(&dir, "main.glsl");
+        assert_eq!(
+            expanded.unwrap(),
+            "a\n#line 1 3\nc\n#line 1 4\ne\n#line 3 3\nd\n#line 3 2\nb\n"
+        );
+        assert_eq!(preprocessor.files, [dir.join("x.glsl"), dir.join("y.glsl")]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn search_order() {
+        // quoted looks next to the file first, <> only in the search path
+        let dir = project(
+            "search-order",
+            &[
+                (
+                    "pass/main.glsl",
+                    "#include \"util.glsl\"\n#include <util.glsl>\n",
+                ),
+                ("pass/util.glsl", "next to the pass\n"),
+                ("util.glsl", "in the project\n"),
+            ],
+        );
+        let (expanded, preprocessor) = expand(&dir, "pass/main.glsl");
+        assert_eq!(
+            expanded.unwrap(),
+            "#line 1 3\nnext to the pass\n#line 2 2\n#line 1 4\nin the project\n#line 3 2\n"
+        );
+        assert_eq!(
+            preprocessor.files,
+            [dir.join("pass/util.glsl"), dir.join("util.glsl")]
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn include_cycle() {
+        let dir = project(
+            "include-cycle",
+            &[
+                ("main.glsl", "#include \"a.glsl\"\n"),
+                ("a.glsl", "\n#include \"b.glsl\"\n"),
+                ("b.glsl", "#include \"a.glsl\"\n"),
+            ],
+        );
+        let (expanded, preprocessor) = expand(&dir, "main.glsl");
+        assert_eq!(expanded, None);
+        let error = &preprocessor.errors[0];
+        assert_eq!(
+            (error.file.as_deref(), error.line, error.message.as_str()),
+            (
+                Some("b.glsl"),
+                Some(1),
+                "include cycle: main.glsl -> a.glsl -> b.glsl -> a.glsl"
+            )
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_include() {
+        let dir = project(
+            "missing-include",
+            &[("main.glsl", "a\n#include <nope.glsl>\n")],
+        );
+        let (expanded, preprocessor) = expand(&dir, "main.glsl");
+        assert_eq!(expanded, None);
+        let error = &preprocessor.errors[0];
+        assert_eq!(
+            (error.file.as_deref(), error.line, error.message.as_str()),
+            (Some("main.glsl"), Some(2), "can't find include nope.glsl")
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn library_include() {
+        let dir = project(
+            "library-include",
+            &[("main.glsl", "#include <shadercrab/hash.glsl>\n")],
+        );
+        let (expanded, preprocessor) = expand(&dir, "main.glsl");
+        // library files come from the library, even the ones they include
+        assert!(expanded.unwrap().contains("float hash11(float p) {"));
+        assert_eq!(
+            preprocessor.files,
+            [
+                PathBuf::from("<shadercrab/hash.glsl>"),
+                PathBuf::from("<shadercrab/version.glsl>")
+            ]
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn source_names() {
+        let names = ["inputs", "common.glsl", "main.glsl", "util.glsl"].map(String::from);
+        let log = "0:4(2): error: nope\nERROR: 3:7: 'x' : undeclared\n2(5) : error C1008: x\n9:1(1): error: unknown source\nno number";
+        assert_eq!(
+            name_sources(log, &names),
+            "inputs:4(2): error: nope\nERROR: util.glsl:7: 'x' : undeclared\nmain.glsl(5) : error C1008: x\n9:1(1): error: unknown source\nno number"
+        );
+    }
+}
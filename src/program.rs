@@ -1,38 +1,236 @@
-// load a shader program
-pub fn load_program(
-    display: &glium::Display,
-    shader: &str,
+use glium::backend::Facade;
+use glium::program::{Binary, ProgramCreationError, ProgramCreationInput};
+use glium::Program;
+use glslang::error::GlslangError;
+use glslang::{
+    Compiler, CompilerOptions, Shader, ShaderInput, ShaderMessage, ShaderSource, ShaderStage,
+    SourceLanguage, Target,
+};
+
+use crate::args::MessageFormat;
+use crate::cache::Cache;
+use crate::diagnostic::report;
+
+/// glsl version to compile shaders as
+#[derive(Copy, Clone, PartialEq, Eq, Default)]
+pub enum Version {
+    #[default]
+    Glsl140,
+    Glsl330,
+    Glsl450,
+    // what shadertoy uses, with webgl2
+    Es300,
+}
+
+impl std::str::FromStr for Version {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "140" => Ok(Self::Glsl140),
+            "330" => Ok(Self::Glsl330),
+            "450" => Ok(Self::Glsl450),
+            "es300" => Ok(Self::Es300),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Version {
+    // goes at the start of every shader
+    fn header(self) -> &'static str {
+        match self {
+            Self::Glsl140 => "#version 140",
+            Self::Glsl330 => "#version 330 core",
+            Self::Glsl450 => "#version 450 core",
+            // es defaults to less precision, which would lose precision in the buffers
+            Self::Es300 => "#version 300 es\nprecision highp float;\nprecision highp int;\nprecision highp sampler2D;",
+        }
+    }
+}
+
+// full source of a fragment shader
+// with located outputs, every output says where it goes
+fn fragment_source(
+    header: &str,
+    located: bool,
     common: &str,
-) -> Option<glium::program::Program> {
-    // load build-in shaders
-    let vertex_shader = include_str!("vertex.vert");
+    shader: &str,
+    attachments: usize,
+    defines: &[(String, String)],
+) -> String {
+    // one output per attachment, all given to mainImage in order
+    let outputs = (0..attachments)
+        .map(|i| match located {
+            false => format!("out vec4 _internal_fragcol{};", i),
+            true => format!("layout(location = {0}) out vec4 _internal_fragcol{0};", i),
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    let names = (0..attachments)
+        .map(|i| format!("_internal_fragcol{}", i))
+        .collect::<Vec<String>>();
+
+    let defines = defines
+        .iter()
+        .map(|(name, value)| format!("#define {} {}", name, value))
+        .collect::<Vec<String>>()
+        .join("\n");
 
     // format the shader so it can go from shadertoy -> opengl
-    let formatted_shader = format!(include_str!("fragment.frag"), common, shader);
+    format!(
+        include_str!("fragment.frag"),
+        version = header,
+        defines = defines,
+        outputs = outputs,
+        inputs = include_str!("inputs.glsl"),
+        common = common,
+        shader = shader,
+        arguments = names.join(", "),
+        assign = names.join(" = "),
+    )
+}
+
+/// everything needed to compile the program of a pass
+#[derive(Clone)]
+pub struct ProgramSource {
+    // name of the pass, and of the file for every source number, to show in errors
+    pub pass: String,
+    pub names: Vec<String>,
+
+    pub vertex: String,
+    pub fragment: String,
+
+    // version it's compiled as, es is also checked against the spec before the driver sees it
+    pub version: Version,
+}
+
+impl ProgramSource {
+    /// source of the program of a pass, names has the name of the file for every source number
+    pub fn new(
+        pass: &str,
+        names: Vec<String>,
+        common: &str,
+        shader: &str,
+        attachments: usize,
+        defines: &[(String, String)],
+        version: Version,
+    ) -> Self {
+        // load build-in shaders
+        let vertex = format!(include_str!("vertex.vert"), version = version.header());
+
+        // 140 can't set where outputs go, the others need to with multiple outputs in es
+        let fragment = fragment_source(
+            version.header(),
+            version != Version::Glsl140,
+            common,
+            shader,
+            attachments,
+            defines,
+        );
+
+        Self {
+            pass: pass.to_string(),
+            names,
+            vertex,
+            fragment,
+            version,
+        }
+    }
+}
+
+// load a shader program
+// gives the compiler log if it fails
+pub fn load_program<F: Facade>(facade: &F, source: &ProgramSource) -> Result<Program, String> {
+    // drivers accept more than webgl2 does, so es is checked with glslang first
+    if source.version == Version::Es300 {
+        validate(&source.vertex, ShaderStage::Vertex)?;
+        validate(&source.fragment, ShaderStage::Fragment)?;
+    }
 
     // make the shader input, because from_source does not give the ability to set srgb output
-    let shader_input = glium::program::ProgramCreationInput::SourceCode {
-        vertex_shader,
+    let shader_input = ProgramCreationInput::SourceCode {
+        vertex_shader: &source.vertex,
         tessellation_control_shader: None,
         tessellation_evaluation_shader: None,
         geometry_shader: None,
-        fragment_shader: &formatted_shader,
+        fragment_shader: &source.fragment,
         transform_feedback_varyings: None,
         outputs_srgb: true,
         uses_point_size: false,
     };
 
     // make the program to run the shader
-    match glium::program::Program::new(display, shader_input) {
-        Ok(x) => Some(x),
-        Err(reason) => {
-            let error = match reason {
-                glium::program::ProgramCreationError::CompilationError(e, _) => e,
-                x => format!("Error: {:?}", x),
-            };
-
-            println!("Failed to compile shader:\n{}", error);
+    Program::new(facade, shader_input).map_err(|reason| match reason {
+        ProgramCreationError::CompilationError(e, _) => e,
+        x => format!("Error: {:?}", x),
+    })
+}
+
+/// parse and validate a shader with glslang, which follows the spec instead of what a driver allows
+/// gives the log if it fails, in the same format amd uses
+pub fn validate(source: &str, stage: ShaderStage) -> Result<(), String> {
+    let compiler = Compiler::acquire().ok_or("Failed to start glslang")?;
+    let source = ShaderSource::from(source);
+    let options = CompilerOptions {
+        source_language: SourceLanguage::GLSL,
+        target: Target::None(None),
+        version_profile: None,
+        messages: ShaderMessage::DEFAULT,
+    };
+    let input = ShaderInput::new(
+        &source,
+        stage,
+        &options,
+        None::<&[(&str, Option<&str>)]>,
+        None,
+    )
+    .map_err(|x| format!("Error: {}", x))?;
+
+    match Shader::new(compiler, input).parse() {
+        Ok(_) => Ok(()),
+        Err(GlslangError::ParseError(log) | GlslangError::PreprocessError(log)) => Err(log.log),
+        Err(x) => Err(format!("Error: {}", x)),
+    }
+}
+
+/// compile a program, or load it from the cache, and print why if it fails
+pub fn compile<F: Facade>(
+    facade: &F,
+    source: &ProgramSource,
+    format: MessageFormat,
+    cache: Option<&Cache>,
+) -> Option<Program> {
+    // cached binaries can stop loading, such as after a driver update, then it's compiled again
+    if let Some(cache) = cache {
+        if let Some(binary) = cache.load(facade, source) {
+            match load_binary(facade, binary) {
+                Some(program) => return Some(program),
+                None => cache.remove(facade, source),
+            }
+        }
+    }
+
+    match load_program(facade, source) {
+        Ok(x) => {
+            if let (Some(cache), Ok(binary)) = (cache, x.get_binary()) {
+                cache.store(facade, source, &binary);
+            }
+            Some(x)
+        }
+        Err(log) => {
+            report(&log, &source.names, &source.pass, format);
             None
         }
     }
 }
+
+/// load a program from the binary of one compiled before, on any context of the same driver
+pub fn load_binary<F: Facade>(facade: &F, binary: Binary) -> Option<Program> {
+    let input = ProgramCreationInput::Binary {
+        data: binary,
+        outputs_srgb: true,
+        uses_point_size: false,
+    };
+    Program::new(facade, input).ok()
+}
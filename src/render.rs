@@ -0,0 +1,217 @@
+use glium::glutin;
+use image::imageops::FilterType;
+use image::{Rgba32FImage, RgbaImage};
+use std::path::Path;
+
+use crate::args::{Arguments, Downsample};
+use crate::buffer::FrameInfo;
+use crate::clock::Clock;
+use crate::diagnostic::message;
+use crate::drawer::Drawer;
+use crate::parser::Shadertoy;
+
+/// frames per second when rendering offline, if not set
+pub const DEFAULT_FPS: f32 = 60.0;
+
+/// make a display that's never shown, without vsync
+/// the event loop has to be kept around for as long as the display is used
+pub fn hidden_display(
+    width: u32,
+    height: u32,
+) -> (glutin::event_loop::EventLoop<()>, glium::Display) {
+    // we still need a window for the context, but it's never shown
+    let event_loop = glutin::event_loop::EventLoop::new();
+    let wb = glutin::window::WindowBuilder::new()
+        .with_visible(false)
+        .with_inner_size(glutin::dpi::PhysicalSize::new(width, height));
+    let cb = glutin::ContextBuilder::new().with_vsync(false);
+    let display = glium::Display::new(wb, cb, &event_loop).unwrap();
+    (event_loop, display)
+}
+
+/// render the shader without showing it, and save the result
+pub fn render(args: &Arguments, shadertoy: &Shadertoy, output: &Path) {
+    let (_event_loop, display) = hidden_display(args.size.0, args.size.1);
+
+    // accumulating from the arguments takes priority over the config
+    let accumulate = args.accumulate.or_else(|| shadertoy.accumulate());
+
+    // supersampling is just rendering at a higher scale
+    let scale = args.render_scale * args.supersample as f32;
+
+    // the actual drawing manager
+    let resolution = display.get_framebuffer_dimensions();
+    let mut drawer = Drawer::new(&display, resolution.0, resolution.1, scale, accumulate);
+    drawer.nan_check.enabled = args.check_nan;
+    drawer.message_format = args.message_format;
+    shadertoy.load_shaders(&display, &mut drawer);
+
+    // always use a fixed timestep, so renders are the same every time
+    let mut clock = Clock::new(
+        args.start_time,
+        args.speed,
+        false,
+        Some(args.fixed.unwrap_or(DEFAULT_FPS)),
+    );
+
+    // inputs for a frame, with sub-frames spread over the shutter time for motion blur
+    let frame_info = |clock: &Clock, frame: i32, sub_frame: u32| FrameInfo {
+        time: clock.time()
+            + sub_frame as f32 / args.motion_blur as f32 * args.shutter / 360.0 * clock.delta(),
+        time_delta: clock.delta(),
+        frame_rate: clock.frame_rate(),
+        frame,
+        ..Default::default()
+    };
+
+    let mut frame = 0;
+    let image = match accumulate {
+        // render until all samples are taken, all at the start time
+        // motion blur is done by spreading the samples over the shutter time
+        Some(_) => {
+            while !drawer.converged() {
+                let info = frame_info(&clock, frame, drawer.samples % args.motion_blur);
+                drawer.draw(&display, &info, scale);
+                frame += 1;
+            }
+            linear(read_texture(&drawer.output(&display)))
+        }
+        // render all frames, and average the sub-frames of the last one
+        // only the main pass is run again for the sub-frames, so simulations in buffers don't take extra steps
+        None => {
+            let mut image = Rgba32FImage::new(drawer.width, drawer.height);
+            let frames = args.frames.unwrap_or(1) as i32;
+            while frame < frames {
+                let last = frame + 1 == frames;
+                for sub_frame in 0..if last { args.motion_blur } else { 1 } {
+                    let info = frame_info(&clock, clock.frame(), sub_frame);
+                    match sub_frame {
+                        0 => drawer.draw(&display, &info, scale),
+                        _ => drawer.draw_main(&display, &info),
+                    }
+
+                    // add the sub-frame to the average
+                    if last {
+                        let sample = linear(read_texture(&drawer.output(&display)));
+                        for (x, y) in image.pixels_mut().zip(sample.pixels()) {
+                            for (x, y) in x.0.iter_mut().zip(y.0) {
+                                *x += y / args.motion_blur as f32;
+                            }
+                        }
+                    }
+                }
+                clock.tick();
+                frame += 1;
+            }
+            image
+        }
+    };
+
+    // go back to the requested resolution
+    // averaging is done in linear color, as averaging sRGB darkens edges
+    let image = match args.downsample {
+        _ if args.supersample == 1 => image,
+        Downsample::Box => box_downsample(&image, args.supersample),
+        Downsample::Lanczos => image::imageops::resize(
+            &image,
+            image.width() / args.supersample,
+            image.height() / args.supersample,
+            FilterType::Lanczos3,
+        ),
+    };
+
+    // and save it
+    match save_image(srgb(image), output) {
+        Ok(()) => message(
+            args.message_format,
+            &format!("Rendered {} frames to {:?}", frame, output),
+        ),
+        Err(reason) => {
+            let text = format!("Failed to save image {:?}: {}", output, reason);
+            message(args.message_format, &text);
+            std::process::exit(1);
+        }
+    }
+
+    // fail if any pass had NaN or Inf, so this can be used as a check
+    if drawer.nan_check.found.is_some() {
+        std::process::exit(1);
+    }
+}
+
+// read back a texture, with the first row at the top like images
+fn read_texture(texture: &glium::Texture2d) -> Rgba32FImage {
+    let (width, height) = texture.dimensions();
+    let pixels: Vec<Vec<(f32, f32, f32, f32)>> = texture
+        .main_level()
+        .first_layer()
+        .into_image(None)
+        .expect("failed to get texture image")
+        .raw_read(&glium::Rect {
+            left: 0,
+            bottom: 0,
+            width,
+            height,
+        });
+
+    // opengl has the first row at the bottom
+    Rgba32FImage::from_fn(width, height, |x, y| {
+        let (r, g, b, a) = pixels[(height - y - 1) as usize][x as usize];
+        image::Rgba([r, g, b, a])
+    })
+}
+
+// average every factor by factor block of pixels
+fn box_downsample(image: &Rgba32FImage, factor: u32) -> Rgba32FImage {
+    Rgba32FImage::from_fn(image.width() / factor, image.height() / factor, |x, y| {
+        let mut sum = [0.0; 4];
+        for i in 0..factor * factor {
+            let pixel = image.get_pixel(x * factor + i % factor, y * factor + i / factor);
+            for (x, y) in sum.iter_mut().zip(pixel.0) {
+                *x += y / (factor * factor) as f32;
+            }
+        }
+        image::Rgba(sum)
+    })
+}
+
+// convert an image from the sRGB the shader outputs to linear color, alpha stays the same
+fn linear(mut image: Rgba32FImage) -> Rgba32FImage {
+    for pixel in image.pixels_mut() {
+        for x in &mut pixel.0[..3] {
+            *x = if *x <= 0.04045 {
+                *x / 12.92
+            } else {
+                ((*x + 0.055) / 1.055).powf(2.4)
+            };
+        }
+    }
+    image
+}
+
+// and back to sRGB
+fn srgb(mut image: Rgba32FImage) -> Rgba32FImage {
+    for pixel in image.pixels_mut() {
+        for x in &mut pixel.0[..3] {
+            *x = if *x <= 0.0031308 {
+                *x * 12.92
+            } else {
+                1.055 * x.powf(1.0 / 2.4) - 0.055
+            };
+        }
+    }
+    image
+}
+
+// save an image, converted to 8 bits
+fn save_image(image: Rgba32FImage, path: &Path) -> image::ImageResult<()> {
+    // the shader output is already in sRGB, so only convert to 8 bits
+    let mut image: RgbaImage = image::DynamicImage::ImageRgba32F(image).into_rgba8();
+
+    // alpha is ignored on screen, so it is here too
+    for pixel in image.pixels_mut() {
+        pixel.0[3] = 255;
+    }
+
+    image.save(path)
+}
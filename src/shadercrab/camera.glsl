@@ -0,0 +1,27 @@
+// cameras for raymarching
+#ifndef SHADERCRAB_CAMERA
+#define SHADERCRAB_CAMERA
+#include <shadercrab/version.glsl>
+
+// from camera space to world space, for a camera at origin looking at target, rolled by roll radians
+// camera space has x to the right, y up, and z forward
+mat3 cameraMatrix(vec3 origin, vec3 target, float roll) {
+	vec3 w = normalize(target - origin);
+	vec3 u = normalize(cross(w, vec3(sin(roll), cos(roll), 0.0)));
+	vec3 v = cross(u, w);
+	return mat3(u, v, w);
+}
+
+// direction of the ray through fragCoord, for a camera at origin looking at target
+// zoom is the focal length, 1 gives a vertical field of view of 90 degrees
+vec3 cameraRay(vec2 fragCoord, vec2 resolution, vec3 origin, vec3 target, float zoom) {
+	vec2 uv = (2.0 * fragCoord - resolution) / resolution.y;
+	return cameraMatrix(origin, target, 0.0) * normalize(vec3(uv, zoom));
+}
+
+// position of a camera orbiting the origin, yaw and pitch are in radians
+vec3 orbitCamera(float yaw, float pitch, float radius) {
+	return radius * vec3(cos(pitch) * sin(yaw), sin(pitch), cos(pitch) * cos(yaw));
+}
+
+#endif
@@ -0,0 +1,65 @@
+// colour conversions, rgb is linear unless it says srgb
+#ifndef SHADERCRAB_COLOR
+#define SHADERCRAB_COLOR
+#include <shadercrab/version.glsl>
+
+vec3 srgbToLinear(vec3 c) {
+	vec3 high = pow(max((c + 0.055) / 1.055, 0.0), vec3(2.4));
+	return mix(c / 12.92, high, step(0.04045, c));
+}
+
+vec3 linearToSrgb(vec3 c) {
+	vec3 high = 1.055 * pow(max(c, 0.0), vec3(1.0 / 2.4)) - 0.055;
+	return mix(c * 12.92, high, step(0.0031308, c));
+}
+
+// hue, saturation and value all go from 0 to 1
+vec3 rgbToHsv(vec3 c) {
+	vec4 k = vec4(0.0, -1.0 / 3.0, 2.0 / 3.0, -1.0);
+	vec4 p = mix(vec4(c.bg, k.wz), vec4(c.gb, k.xy), step(c.b, c.g));
+	vec4 q = mix(vec4(p.xyw, c.r), vec4(c.r, p.yzx), step(p.x, c.r));
+	float d = q.x - min(q.w, q.y);
+	float e = 1.0e-10;
+	return vec3(abs(q.z + (q.w - q.y) / (6.0 * d + e)), d / (q.x + e), q.x);
+}
+
+vec3 hsvToRgb(vec3 c) {
+	vec3 p = abs(fract(c.xxx + vec3(1.0, 2.0 / 3.0, 1.0 / 3.0)) * 6.0 - 3.0);
+	return c.z * mix(vec3(1.0), clamp(p - 1.0, 0.0, 1.0), c.y);
+}
+
+// oklab, from https://bottosson.github.io/posts/oklab
+vec3 rgbToOklab(vec3 c) {
+	vec3 lms = vec3(
+		0.4122214708 * c.r + 0.5363325363 * c.g + 0.0514459929 * c.b,
+		0.2119034982 * c.r + 0.6806995451 * c.g + 0.1073969566 * c.b,
+		0.0883024619 * c.r + 0.2817188376 * c.g + 0.6299787005 * c.b
+	);
+	lms = sign(lms) * pow(abs(lms), vec3(1.0 / 3.0));
+	return vec3(
+		0.2104542553 * lms.x + 0.7936177850 * lms.y - 0.0040720468 * lms.z,
+		1.9779984951 * lms.x - 2.4285922050 * lms.y + 0.4505937099 * lms.z,
+		0.0259040371 * lms.x + 0.7827717662 * lms.y - 0.8086757660 * lms.z
+	);
+}
+
+vec3 oklabToRgb(vec3 c) {
+	vec3 lms = vec3(
+		c.x + 0.3963377774 * c.y + 0.2158037573 * c.z,
+		c.x - 0.1055613458 * c.y - 0.0638541728 * c.z,
+		c.x - 0.0894841775 * c.y - 1.2914855480 * c.z
+	);
+	lms = lms * lms * lms;
+	return vec3(
+		4.0767416621 * lms.x - 3.3077115913 * lms.y + 0.2309699292 * lms.z,
+		-1.2684380046 * lms.x + 2.6097574011 * lms.y - 0.3413193965 * lms.z,
+		-0.0041960863 * lms.x - 0.7034186147 * lms.y + 1.7076147010 * lms.z
+	);
+}
+
+// relative luminance, with the rec. 709 weights
+float luminance(vec3 c) {
+	return dot(c, vec3(0.2126, 0.7152, 0.0722));
+}
+
+#endif
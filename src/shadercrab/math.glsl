@@ -0,0 +1,45 @@
+// rotations, angles are in radians
+#ifndef SHADERCRAB_MATH
+#define SHADERCRAB_MATH
+#include <shadercrab/version.glsl>
+
+// counter clockwise
+mat2 rotate2d(float angle) {
+	float c = cos(angle);
+	float s = sin(angle);
+	return mat2(c, s, -s, c);
+}
+
+// counter clockwise when looking down the axis, towards the origin
+mat3 rotateX(float angle) {
+	float c = cos(angle);
+	float s = sin(angle);
+	return mat3(1.0, 0.0, 0.0, 0.0, c, s, 0.0, -s, c);
+}
+
+mat3 rotateY(float angle) {
+	float c = cos(angle);
+	float s = sin(angle);
+	return mat3(c, 0.0, -s, 0.0, 1.0, 0.0, s, 0.0, c);
+}
+
+mat3 rotateZ(float angle) {
+	float c = cos(angle);
+	float s = sin(angle);
+	return mat3(c, s, 0.0, -s, c, 0.0, 0.0, 0.0, 1.0);
+}
+
+// around any axis, which doesn't have to be normalized
+mat3 rotateAxis(vec3 axis, float angle) {
+	vec3 a = normalize(axis);
+	float c = cos(angle);
+	float s = sin(angle);
+	vec3 o = a * (1.0 - c);
+	return mat3(
+		o.x * a.x + c, o.x * a.y + a.z * s, o.x * a.z - a.y * s,
+		o.x * a.y - a.z * s, o.y * a.y + c, o.y * a.z + a.x * s,
+		o.x * a.z + a.y * s, o.y * a.z - a.x * s, o.z * a.z + c
+	);
+}
+
+#endif
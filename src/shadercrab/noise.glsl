@@ -0,0 +1,146 @@
+// noise in 2d and 3d
+// value, perlin and simplex noise go from about -1 to 1, worley noise is the distance to the closest point
+#ifndef SHADERCRAB_NOISE
+#define SHADERCRAB_NOISE
+#include <shadercrab/version.glsl>
+#include <shadercrab/hash.glsl>
+
+// interpolates between random values on a grid
+float valueNoise(vec2 p) {
+	vec2 i = floor(p);
+	vec2 f = fract(p);
+	vec2 u = f * f * (3.0 - 2.0 * f);
+	float n = mix(
+		mix(hash12(i), hash12(i + vec2(1.0, 0.0)), u.x),
+		mix(hash12(i + vec2(0.0, 1.0)), hash12(i + vec2(1.0, 1.0)), u.x),
+		u.y
+	);
+	return n * 2.0 - 1.0;
+}
+
+float valueNoise(vec3 p) {
+	vec3 i = floor(p);
+	vec3 f = fract(p);
+	vec3 u = f * f * (3.0 - 2.0 * f);
+	float n = mix(
+		mix(
+			mix(hash13(i), hash13(i + vec3(1.0, 0.0, 0.0)), u.x),
+			mix(hash13(i + vec3(0.0, 1.0, 0.0)), hash13(i + vec3(1.0, 1.0, 0.0)), u.x),
+			u.y
+		),
+		mix(
+			mix(hash13(i + vec3(0.0, 0.0, 1.0)), hash13(i + vec3(1.0, 0.0, 1.0)), u.x),
+			mix(hash13(i + vec3(0.0, 1.0, 1.0)), hash13(i + vec3(1.0, 1.0, 1.0)), u.x),
+			u.y
+		),
+		u.z
+	);
+	return n * 2.0 - 1.0;
+}
+
+// interpolates between random gradients on a grid
+float perlinNoise(vec2 p) {
+	vec2 i = floor(p);
+	vec2 f = fract(p);
+	vec2 u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+	float a = dot(hash22(i) * 2.0 - 1.0, f);
+	float b = dot(hash22(i + vec2(1.0, 0.0)) * 2.0 - 1.0, f - vec2(1.0, 0.0));
+	float c = dot(hash22(i + vec2(0.0, 1.0)) * 2.0 - 1.0, f - vec2(0.0, 1.0));
+	float d = dot(hash22(i + vec2(1.0, 1.0)) * 2.0 - 1.0, f - vec2(1.0, 1.0));
+	return mix(mix(a, b, u.x), mix(c, d, u.x), u.y);
+}
+
+float perlinNoise(vec3 p) {
+	vec3 i = floor(p);
+	vec3 f = fract(p);
+	vec3 u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+	float n000 = dot(hash33(i) * 2.0 - 1.0, f);
+	float n100 = dot(hash33(i + vec3(1.0, 0.0, 0.0)) * 2.0 - 1.0, f - vec3(1.0, 0.0, 0.0));
+	float n010 = dot(hash33(i + vec3(0.0, 1.0, 0.0)) * 2.0 - 1.0, f - vec3(0.0, 1.0, 0.0));
+	float n110 = dot(hash33(i + vec3(1.0, 1.0, 0.0)) * 2.0 - 1.0, f - vec3(1.0, 1.0, 0.0));
+	float n001 = dot(hash33(i + vec3(0.0, 0.0, 1.0)) * 2.0 - 1.0, f - vec3(0.0, 0.0, 1.0));
+	float n101 = dot(hash33(i + vec3(1.0, 0.0, 1.0)) * 2.0 - 1.0, f - vec3(1.0, 0.0, 1.0));
+	float n011 = dot(hash33(i + vec3(0.0, 1.0, 1.0)) * 2.0 - 1.0, f - vec3(0.0, 1.0, 1.0));
+	float n111 = dot(hash33(i + vec3(1.0, 1.0, 1.0)) * 2.0 - 1.0, f - vec3(1.0, 1.0, 1.0));
+	return mix(
+		mix(mix(n000, n100, u.x), mix(n010, n110, u.x), u.y),
+		mix(mix(n001, n101, u.x), mix(n011, n111, u.x), u.y),
+		u.z
+	);
+}
+
+// random gradients on a grid of triangles, from https://www.shadertoy.com/view/Msf3WH
+float simplexNoise(vec2 p) {
+	const float K1 = 0.366025404; // (sqrt(3) - 1) / 2
+	const float K2 = 0.211324865; // (3 - sqrt(3)) / 6
+	vec2 i = floor(p + (p.x + p.y) * K1);
+	vec2 a = p - i + (i.x + i.y) * K2;
+	float m = step(a.y, a.x);
+	vec2 o = vec2(m, 1.0 - m);
+	vec2 b = a - o + K2;
+	vec2 c = a - 1.0 + 2.0 * K2;
+	vec3 h = max(0.5 - vec3(dot(a, a), dot(b, b), dot(c, c)), 0.0);
+	vec3 n = h * h * h * h * vec3(
+		dot(a, hash22(i) * 2.0 - 1.0),
+		dot(b, hash22(i + o) * 2.0 - 1.0),
+		dot(c, hash22(i + 1.0) * 2.0 - 1.0)
+	);
+	return dot(n, vec3(70.0));
+}
+
+// random gradients on a grid of tetrahedrons, from https://www.shadertoy.com/view/XsX3zB
+float simplexNoise(vec3 p) {
+	const float F3 = 0.3333333;
+	const float G3 = 0.1666667;
+	vec3 s = floor(p + dot(p, vec3(F3)));
+	vec3 x = p - s + dot(s, vec3(G3));
+	vec3 e = step(vec3(0.0), x - x.yzx);
+	vec3 i1 = e * (1.0 - e.zxy);
+	vec3 i2 = 1.0 - e.zxy * (1.0 - e);
+	vec3 x1 = x - i1 + G3;
+	vec3 x2 = x - i2 + 2.0 * G3;
+	vec3 x3 = x - 1.0 + 3.0 * G3;
+	vec4 w = max(0.6 - vec4(dot(x, x), dot(x1, x1), dot(x2, x2), dot(x3, x3)), 0.0);
+	vec4 d = vec4(
+		dot(hash33(s) - 0.5, x),
+		dot(hash33(s + i1) - 0.5, x1),
+		dot(hash33(s + i2) - 0.5, x2),
+		dot(hash33(s + 1.0) - 0.5, x3)
+	);
+	w *= w;
+	w *= w;
+	return dot(d * w, vec4(52.0));
+}
+
+// distance to the closest of the random points, one in every grid cell
+float worleyNoise(vec2 p) {
+	vec2 i = floor(p);
+	vec2 f = fract(p);
+	float closest = 8.0;
+	for (int y = -1; y <= 1; y++) {
+		for (int x = -1; x <= 1; x++) {
+			vec2 cell = vec2(x, y);
+			vec2 offset = cell + hash22(i + cell) - f;
+			closest = min(closest, dot(offset, offset));
+		}
+	}
+	return sqrt(closest);
+}
+
+float worleyNoise(vec3 p) {
+	vec3 i = floor(p);
+	vec3 f = fract(p);
+	float closest = 8.0;
+	for (int z = -1; z <= 1; z++) {
+		for (int y = -1; y <= 1; y++) {
+			for (int x = -1; x <= 1; x++) {
+				vec3 cell = vec3(x, y, z);
+				vec3 offset = cell + hash33(i + cell) - f;
+				closest = min(closest, dot(offset, offset));
+			}
+		}
+	}
+	return sqrt(closest);
+}
+
+#endif
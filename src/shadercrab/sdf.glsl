@@ -0,0 +1,110 @@
+// signed distance functions, from https://iquilezles.org/articles/distfunctions
+// shapes are centered at the origin, negative inside
+#ifndef SHADERCRAB_SDF
+#define SHADERCRAB_SDF
+#include <shadercrab/version.glsl>
+
+float sdSphere(vec3 p, float radius) {
+	return length(p) - radius;
+}
+
+// size is half the size of the box
+float sdBox(vec3 p, vec3 size) {
+	vec3 q = abs(p) - size;
+	return length(max(q, 0.0)) + min(max(q.x, max(q.y, q.z)), 0.0);
+}
+
+float sdRoundBox(vec3 p, vec3 size, float radius) {
+	vec3 q = abs(p) - size + radius;
+	return length(max(q, 0.0)) + min(max(q.x, max(q.y, q.z)), 0.0) - radius;
+}
+
+// around the y axis, radii are of the ring and the tube
+float sdTorus(vec3 p, vec2 radii) {
+	vec2 q = vec2(length(p.xz) - radii.x, p.y);
+	return length(q) - radii.y;
+}
+
+// along the y axis, height is half the height
+float sdCylinder(vec3 p, float height, float radius) {
+	vec2 d = abs(vec2(length(p.xz), p.y)) - vec2(radius, height);
+	return min(max(d.x, d.y), 0.0) + length(max(d, 0.0));
+}
+
+// from a to b
+float sdCapsule(vec3 p, vec3 a, vec3 b, float radius) {
+	vec3 pa = p - a;
+	vec3 ba = b - a;
+	float h = clamp(dot(pa, ba) / dot(ba, ba), 0.0, 1.0);
+	return length(pa - ba * h) - radius;
+}
+
+// normal has to be normalized, height is how far below the origin the plane is
+float sdPlane(vec3 p, vec3 normal, float height) {
+	return dot(p, normal) + height;
+}
+
+float sdCircle(vec2 p, float radius) {
+	return length(p) - radius;
+}
+
+float sdBox(vec2 p, vec2 size) {
+	vec2 d = abs(p) - size;
+	return length(max(d, 0.0)) + min(max(d.x, d.y), 0.0);
+}
+
+float sdSegment(vec2 p, vec2 a, vec2 b) {
+	vec2 pa = p - a;
+	vec2 ba = b - a;
+	float h = clamp(dot(pa, ba) / dot(ba, ba), 0.0, 1.0);
+	return length(pa - ba * h);
+}
+
+// combining shapes, subtraction is a minus b
+float opUnion(float a, float b) {
+	return min(a, b);
+}
+
+float opSubtraction(float a, float b) {
+	return max(a, -b);
+}
+
+float opIntersection(float a, float b) {
+	return max(a, b);
+}
+
+// same, but blended over a distance of about k
+float opSmoothUnion(float a, float b, float k) {
+	float h = clamp(0.5 + 0.5 * (b - a) / k, 0.0, 1.0);
+	return mix(b, a, h) - k * h * (1.0 - h);
+}
+
+float opSmoothSubtraction(float a, float b, float k) {
+	float h = clamp(0.5 - 0.5 * (a + b) / k, 0.0, 1.0);
+	return mix(a, -b, h) + k * h * (1.0 - h);
+}
+
+float opSmoothIntersection(float a, float b, float k) {
+	float h = clamp(0.5 - 0.5 * (b - a) / k, 0.0, 1.0);
+	return mix(b, a, h) + k * h * (1.0 - h);
+}
+
+// changing shapes
+float opRound(float d, float radius) {
+	return d - radius;
+}
+
+float opOnion(float d, float thickness) {
+	return abs(d) - thickness;
+}
+
+// repeats space every spacing, use the result as the position for the shape
+vec2 opRepeat(vec2 p, vec2 spacing) {
+	return p - spacing * round(p / spacing);
+}
+
+vec3 opRepeat(vec3 p, vec3 spacing) {
+	return p - spacing * round(p / spacing);
+}
+
+#endif
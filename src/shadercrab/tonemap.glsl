@@ -0,0 +1,38 @@
+// tonemappers, from linear hdr to linear 0 to 1, except hejl, which is already srgb
+#ifndef SHADERCRAB_TONEMAP
+#define SHADERCRAB_TONEMAP
+#include <shadercrab/version.glsl>
+
+vec3 tonemapReinhard(vec3 c) {
+	return c / (1.0 + c);
+}
+
+// white is the smallest value that becomes 1
+vec3 tonemapReinhard(vec3 c, float white) {
+	return c * (1.0 + c / (white * white)) / (1.0 + c);
+}
+
+// fit of the aces curve, from https://knarkowicz.wordpress.com/2016/01/06/aces-filmic-tone-mapping-curve
+vec3 tonemapAces(vec3 c) {
+	return clamp((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14), 0.0, 1.0);
+}
+
+// curve used by tonemapUncharted2
+vec3 _shadercrab_hable(vec3 x) {
+	const float a = 0.15, b = 0.50, c = 0.10, d = 0.20, e = 0.02, f = 0.30;
+	return (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f;
+}
+
+// from uncharted 2, by john hable
+vec3 tonemapUncharted2(vec3 c) {
+	const float white = 11.2;
+	return _shadercrab_hable(c * 2.0) / _shadercrab_hable(vec3(white));
+}
+
+// by jim hejl and richard burgess-dawson, the result is already srgb
+vec3 tonemapHejl(vec3 c) {
+	vec3 x = max(c - 0.004, 0.0);
+	return (x * (6.2 * x + 0.5)) / (x * (6.2 * x + 1.7) + 0.06);
+}
+
+#endif
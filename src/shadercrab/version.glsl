@@ -0,0 +1,4 @@
+// version of the built in library, functions keep doing the same thing within a version
+#ifndef SHADERCRAB_LIBRARY_VERSION
+#define SHADERCRAB_LIBRARY_VERSION 1
+#endif